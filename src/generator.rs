@@ -1,279 +1,1119 @@
-use crate::parser;
-
-pub fn gen(tree: parser::Program) -> Vec<String> {
-    let prologue = vec!["mv fp,sp".to_owned(), "addi sp,sp,208".to_owned()]; // 26 vars
-    let program = tree
-        .main_function
-        .stmts
-        .into_iter()
-        .map(|s| gen_stmt(s))
-        .flatten()
-        .collect::<Vec<_>>();
-
-    let output: Vec<String> = vec![
-        ".text".to_owned(),
-        ".globl main".to_owned(),
-        ".section .text".to_owned(),
-        "main:".to_owned(),
-        prologue
-            .iter()
-            .map(|line| format!("    {line}"))
-            .collect::<Vec<_>>()
-            .join("\n"),
-        program.join("\n"),
-        // mov rbp rsp
-        // pop rbp
-        "".to_owned(),
+use crate::{parser, target::Target};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use thiserror::Error;
+
+#[derive(Error, Clone, PartialEq, Serialize, Deserialize, Debug)]
+pub enum GenError {
+    #[error("'{name}' undeclared")]
+    Undeclared { name: String },
+    #[error("break statement not within loop or switch")]
+    BreakOutside,
+    #[error("continue statement not within a loop")]
+    ContinueOutside,
+    #[error("case label not within a switch statement")]
+    CaseOutside,
+    #[error("'default' label not within a switch statement")]
+    DefaultOutside,
+    #[error("case label does not reduce to an integer constant")]
+    NotConstant,
+    #[error("duplicate case value {value}")]
+    DuplicateCase { value: i32 },
+    #[error("multiple default labels in one switch")]
+    DuplicateDefault,
+    #[error("floating-point operands are only supported in constants converted to int")]
+    FloatOperand,
+    #[error("parameter '{name}' has an unsupported type")]
+    UnsupportedParam { name: String },
+}
+
+// a function's frame sits below the sp it was called with, which fp (s0)
+// keeps:
+//
+//   -4(fp)   ra
+//   -8(fp)   the caller's fp
+//   -12(fp)  the caller's s1, which keeps sp across calls
+//   -24(fp)  the first variable, and the others every 8 bytes below it
+//
+// and the stack machine pushes below it. arguments after the eighth are
+// passed on the stack, at 0(fp), 4(fp), ...
+const ARG_REGS: usize = 8; // a0-a7
+
+// how a switch finds its case: a jump table when the cases are dense enough
+// that the table has at most TABLE_SPREAD slots for each, a binary decision
+// tree on the sorted values when there are many sparse ones, and otherwise
+// comparing with each in turn
+const TABLE_MIN_CASES: usize = 4;
+const TABLE_SPREAD: i64 = 3;
+const TREE_MIN_CASES: usize = 6;
+
+// calls a libgcc routine on (t2, t1), leaving its result in t3
+fn libcall(name: &str) -> String {
+    [
+        "mv a0,t2".to_owned(),
+        "mv a1,t1".to_owned(),
+        "mv s1,sp".to_owned(),
+        "andi sp,sp,-16".to_owned(),
+        format!("call {name}"),
+        "mv sp,s1".to_owned(),
+        "mv t3,a0".to_owned(),
+    ]
+    .join("\n")
+}
+
+pub fn gen(tree: parser::Program, target: Target) -> Result<Vec<String>, GenError> {
+    let mut generator = Generator {
+        target,
+        labels: 0,
+        scopes: Vec::new(),
+        slots: 0,
+        breaks: Vec::new(),
+        continues: Vec::new(),
+        switches: Vec::new(),
+    };
+    let mut output = vec![".text".to_owned()];
+    for f in tree.functions {
+        output.extend(generator.gen_function(f)?);
+    }
+    output.push("".to_owned());
+
+    Ok(output)
+}
+
+fn epilogue() -> Vec<String> {
+    vec![
+        "# epilogue".to_owned(),
+        "lw ra,-4(fp)".to_owned(),
+        "lw s1,-12(fp)".to_owned(),
+        "mv sp,fp".to_owned(),
+        "lw fp,-8(sp)".to_owned(),
+        "ret".to_owned(),
+    ]
+}
+
+// the value switched on is in t0, and the cases are sorted. values outside
+// the table wrap around to large unsigned ones, so one comparison rules out
+// both sides
+fn jump_table(n: usize, cases: &[(i32, String)], spread: i64, default: &str) -> Vec<String> {
+    let min = cases[0].0;
+    let mut output = vec![
+        format!("li t1,{min}"),
+        "sub t1,t0,t1".to_owned(),
+        format!("li t2,{spread}"),
+        format!("bgeu t1,t2,{default}"),
+        "slli t1,t1,2".to_owned(),
+        format!("la t2,.Ltable{n}"),
+        "add t2,t2,t1".to_owned(),
+        "lw t2,0(t2)".to_owned(),
+        "jr t2".to_owned(),
+        ".section .rodata".to_owned(),
+        ".align 2".to_owned(),
+        format!(".Ltable{n}:"),
     ];
+    let mut cases = cases.iter().peekable();
+    for value in min as i64..min as i64 + spread {
+        match cases.next_if(|(v, _)| *v as i64 == value) {
+            Some((_, label)) => output.push(format!(".word {label}")),
+            None => output.push(format!(".word {default}")),
+        }
+    }
+    output.push(".text".to_owned());
 
     output
 }
 
-fn calc_offset(id: &parser::Id) -> u8 {
-    let binding = id.0.chars().next().unwrap();
-    let offset = binding as u8 - 'a' as u8;
-    offset * 8
+// case values are ints, so they wrap around to 32 bits like the target's
+fn const_eval(e: &parser::Expr) -> Result<i32, GenError> {
+    match e {
+        parser::Expr::Char(c) => Ok(*c as i32),
+        parser::Expr::Int(n)
+        | parser::Expr::UInt(n)
+        | parser::Expr::Long(n)
+        | parser::Expr::ULong(n) => Ok(*n as i32),
+        parser::Expr::UnaryE { op, l } => {
+            let l = const_eval(l)?;
+            match op {
+                parser::UnaryOp::Add => Ok(l),
+                parser::UnaryOp::Sub => Ok(l.wrapping_neg()),
+            }
+        }
+        parser::Expr::BinE { op, l, r } => {
+            let (l, r) = (const_eval(l)?, const_eval(r)?);
+            match op {
+                parser::BinOp::Add => Ok(l.wrapping_add(r)),
+                parser::BinOp::Sub => Ok(l.wrapping_sub(r)),
+                parser::BinOp::Mult => Ok(l.wrapping_mul(r)),
+                parser::BinOp::Div => l.checked_div(r).ok_or(GenError::NotConstant),
+                parser::BinOp::Mod => l.checked_rem(r).ok_or(GenError::NotConstant),
+            }
+        }
+        _ => Err(GenError::NotConstant),
+    }
+}
+
+// whether e has a floating type
+fn is_floating(e: &parser::Expr) -> bool {
+    match e {
+        parser::Expr::Float(_) | parser::Expr::Double(_) | parser::Expr::LongDouble(_) => true,
+        parser::Expr::UnaryE { l, .. } => is_floating(l),
+        parser::Expr::BinE { l, r, .. } => is_floating(l) || is_floating(r),
+        _ => false,
+    }
 }
 
-fn gen_asnmt(a: parser::Asnmt) -> Vec<String> {
-    match a {
-        parser::Asnmt::CreateBind { id, expr } => {
-            let offset = calc_offset(&id);
-            let expr = gen_expr(*expr);
-
-            vec![
-                expr.join("\n"),
-                "# assigning...".to_owned(),
-                "lw t0,0(sp)".to_owned(),
-                format!("sw t0,{offset}(fp)"),
-                "# done...".to_owned(),
-            ]
+// the value of an arithmetic constant expression and the width of its type,
+// 0 for an integer one. float operands are evaluated as floats, as C allows
+fn const_float(e: &parser::Expr) -> Option<(f64, u32)> {
+    match e {
+        parser::Expr::Char(c) => Some((*c as f64, 0)),
+        parser::Expr::Int(n)
+        | parser::Expr::UInt(n)
+        | parser::Expr::Long(n)
+        | parser::Expr::ULong(n) => Some((*n as f64, 0)),
+        parser::Expr::Float(x) => Some((*x as f64, 32)),
+        parser::Expr::Double(x) | parser::Expr::LongDouble(x) => Some((*x, 64)),
+        parser::Expr::UnaryE { op, l } => {
+            let (x, width) = const_float(l)?;
+            match op {
+                parser::UnaryOp::Add => Some((x, width)),
+                parser::UnaryOp::Sub => Some((-x, width)),
+            }
         }
-        parser::Asnmt::UpdateBind { id, op, expr } => {
-            let expr = gen_expr(*expr);
-            let offset = calc_offset(&id);
-            let update = match op {
-                parser::BinOp::Add => "add",
-                parser::BinOp::Sub => "sub",
-                parser::BinOp::Mult => "mul",
-                parser::BinOp::Div => "div",
-                parser::BinOp::Mod => todo!(),
+        parser::Expr::BinE { op, l, r } => {
+            let ((l, lw), (r, rw)) = (const_float(l)?, const_float(r)?);
+            let x = match op {
+                parser::BinOp::Add => l + r,
+                parser::BinOp::Sub => l - r,
+                parser::BinOp::Mult => l * r,
+                parser::BinOp::Div => l / r,
+                parser::BinOp::Mod => return None,
             };
-
-            vec![
-                expr.join("\n"),
-                format!("lw t0,0(sp)").to_owned(),
-                format!("lw t1,{offset}(fp)").to_owned(),
-                format!("{update} t2,t0,t1").to_owned(),
-                format!("sw t2,{offset}(fp)").to_owned(),
-            ]
+            let width = lw.max(rw);
+            Some((if width == 32 { x as f32 as f64 } else { x }, width))
         }
+        _ => None,
     }
 }
 
-fn gen_stmt(s: parser::Stmt) -> Vec<String> {
-    match s {
-        parser::Stmt::Asnmt(a) => gen_asnmt(a),
-        parser::Stmt::For {
-            asnmt,
-            cond,
-            update,
-            body,
-        } => todo!(),
-        parser::Stmt::While => todo!(),
-        parser::Stmt::Return(e) => {
-            let output = vec![
-                gen_expr(e)
-                    .iter()
-                    .map(|line| format!("    {line}"))
-                    .collect::<Vec<_>>()
-                    .join("\n"),
-                "# return expr".to_owned(),
-                "lw a0,0(sp)".to_owned(),
-                "addi sp,sp,8".to_owned(),
-                "ret".to_owned(),
-            ];
+struct Generator {
+    target: Target,
+    labels: usize, // made so far, to keep them unique across functions
+    scopes: Vec<HashMap<parser::Id, i32>>, // variables to their offsets from fp, innermost last
+    slots: usize,  // for the variables of the current function
+    breaks: Vec<String>, // where break jumps to, innermost loop or switch last
+    continues: Vec<String>, // and continue, which switches don't take part in
+    switches: Vec<Switch>, // the cases found so far, innermost switch last
+}
+
+#[derive(Default)]
+struct Switch {
+    cases: Vec<(i32, String)>, // values and their labels
+    default: Option<String>,
+}
 
-            output
+impl Generator {
+    fn label(&mut self) -> usize {
+        self.labels += 1;
+        self.labels
+    }
+
+    // a variable gets a slot of its own, even if it shadows another one
+    fn declare(&mut self, id: parser::Id) -> i32 {
+        let offset = -24 - 8 * self.slots as i32;
+        self.slots += 1;
+        self.scopes.last_mut().unwrap().insert(id, offset);
+        offset
+    }
+
+    fn lookup(&self, id: &parser::Id) -> Result<i32, GenError> {
+        self.scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.get(id))
+            .copied()
+            .ok_or_else(|| GenError::Undeclared {
+                name: id.0.to_string(),
+            })
+    }
+
+    // statements whose declarations go out of scope after them
+    fn gen_block(&mut self, stmts: Vec<parser::Stmt>) -> Result<Vec<String>, GenError> {
+        self.scopes.push(HashMap::new());
+        let output = self.gen_stmts(stmts);
+        self.scopes.pop();
+        output
+    }
+
+    // a loop's body, in which break and continue jump to its labels
+    fn gen_loop_body(
+        &mut self,
+        body: parser::Stmt,
+        brk: String,
+        cont: String,
+    ) -> Result<Vec<String>, GenError> {
+        self.breaks.push(brk);
+        self.continues.push(cont);
+        let output = self.gen_block(vec![body]);
+        self.breaks.pop();
+        self.continues.pop();
+        output
+    }
+
+    fn gen_stmts(&mut self, stmts: Vec<parser::Stmt>) -> Result<Vec<String>, GenError> {
+        let mut output = Vec::new();
+        for s in stmts {
+            output.extend(self.gen_stmt(s)?);
         }
-        parser::Stmt::If => todo!(),
-        parser::Stmt::IfEls { cond, then, els } => {
-            let cond_mc = gen_expr(*cond);
-            let then_mc = gen_stmt(*then);
-            let els_mc = gen_stmt(*els);
-
-            let output = vec![
-                "  ########################### evaluating cond expr ###########################"
-                    .to_owned(),
-                cond_mc
-                    .iter()
-                    .map(|line| format!("  {line}"))
-                    .collect::<Vec<_>>()
-                    .join("\n"),
-                "  ################################### branch ###################################"
-                    .to_owned(),
+
+        Ok(output)
+    }
+
+    fn gen_function(&mut self, f: parser::Function) -> Result<Vec<String>, GenError> {
+        let name = f.name.0;
+
+        // parameters are in the same scope as the body's declarations
+        self.scopes = vec![HashMap::new()];
+        self.slots = 0;
+        let mut params = Vec::new();
+        for (i, p) in f.params.into_iter().enumerate() {
+            // every parameter is taken as one integer register or stack word,
+            // which is how the psABI passes integers and pointers but not
+            // floating values
+            let floating = matches!(
+                p.typ,
+                parser::Type::Float | parser::Type::Double | parser::Type::LongDouble
+            );
+            if floating || p.typ == parser::Type::Void {
+                return Err(GenError::UnsupportedParam {
+                    name: p.id.0.to_string(),
+                });
+            }
+            let offset = self.declare(p.id);
+            if i < ARG_REGS {
+                params.push(format!("sw a{i},{offset}(fp)"));
+            } else {
+                params.push(format!("lw t0,{}(fp)", 4 * (i - ARG_REGS)));
+                params.push(format!("sw t0,{offset}(fp)"));
+            }
+        }
+        let body = self.gen_stmts(f.stmts)?;
+
+        // sp has to stay 16 byte aligned, as the psABI requires
+        let frame = (16 + 8 * self.slots).next_multiple_of(16);
+        let mut prologue = vec![
+            format!("addi sp,sp,-{frame}"),
+            format!("sw ra,{}(sp)", frame - 4),
+            format!("sw fp,{}(sp)", frame - 8),
+            format!("sw s1,{}(sp)", frame - 12),
+            format!("addi fp,sp,{frame}"),
+        ];
+        prologue.extend(params);
+
+        Ok(vec![
+            format!(".globl {name}"),
+            format!("{name}:"),
+            prologue
+                .iter()
+                .map(|line| format!("    {line}"))
+                .collect::<Vec<_>>()
+                .join("\n"),
+            body.join("\n"),
+            // falling off the end returns whatever is in a0
+            epilogue()
+                .iter()
+                .map(|line| format!("    {line}"))
+                .collect::<Vec<_>>()
+                .join("\n"),
+        ])
+    }
+
+    fn gen_asnmt(&mut self, a: parser::Asnmt) -> Result<Vec<String>, GenError> {
+        match a {
+            parser::Asnmt::CreateBind { id, expr } => {
+                let expr = self.gen_converted(*expr)?;
+                let offset = self.declare(id);
+
+                Ok(vec![
+                    expr.join("\n"),
+                    "# assigning...".to_owned(),
+                    "lw t0,0(sp)".to_owned(),
+                    "addi sp,sp,8".to_owned(),
+                    format!("sw t0,{offset}(fp)"),
+                    "# done...".to_owned(),
+                ])
+            }
+            parser::Asnmt::Bind { id, expr } => {
+                let expr = self.gen_converted(*expr)?;
+                let offset = self.lookup(&id)?;
+
+                Ok(vec![
+                    expr.join("\n"),
+                    "lw t0,0(sp)".to_owned(),
+                    "addi sp,sp,8".to_owned(),
+                    format!("sw t0,{offset}(fp)"),
+                ])
+            }
+            parser::Asnmt::UpdateBind { id, op, expr } => {
+                let expr = self.gen_expr(*expr)?;
+                let offset = self.lookup(&id)?;
+
+                Ok(vec![
+                    expr.join("\n"),
+                    "lw t1,0(sp)".to_owned(),
+                    "addi sp,sp,8".to_owned(),
+                    format!("lw t2,{offset}(fp)").to_owned(),
+                    self.gen_binop(op), // x op= e is x = x op e
+                    format!("sw t3,{offset}(fp)").to_owned(),
+                ])
+            }
+        }
+    }
+
+    fn gen_stmt(&mut self, s: parser::Stmt) -> Result<Vec<String>, GenError> {
+        match s {
+            parser::Stmt::Asnmt(a) => self.gen_asnmt(a),
+            parser::Stmt::For {
+                asnmt,
+                cond,
+                update,
+                body,
+            } => {
+                // a variable the first clause declares is only in scope in
+                // the loop
+                self.scopes.push(HashMap::new());
+                let output = self.gen_for(asnmt, cond, update, *body);
+                self.scopes.pop();
+                output
+            }
+            parser::Stmt::While { cond, body } => {
+                let n = self.label();
+                let cond_mc = self.gen_expr(*cond)?;
+                let body_mc =
+                    self.gen_loop_body(*body, format!(".Lend{n}"), format!(".Lcond{n}"))?;
+
+                // the condition is evaluated before every iteration
+                let mut output = vec![format!(".Lcond{n}:")];
+                output.extend(cond_mc.iter().map(|line| format!("  {line}")));
+                output.extend([
+                    "  lw t1,0(sp)".to_owned(),
+                    "  addi sp,sp,8".to_owned(),
+                    format!("  beqz t1,.Lend{n}"),
+                ]);
+                output.extend(body_mc.iter().map(|line| format!("  {line}")));
+                output.push(format!("  j .Lcond{n}"));
+                output.push(format!(".Lend{n}:"));
+
+                Ok(output)
+            }
+            parser::Stmt::DoWhile { body, cond } => {
+                let n = self.label();
+                let body_mc =
+                    self.gen_loop_body(*body, format!(".Lend{n}"), format!(".Lcond{n}"))?;
+                let cond_mc = self.gen_expr(*cond)?;
+
+                // and here after every iteration, starting with the first
+                let mut output = vec![format!(".Lbody{n}:")];
+                output.extend(body_mc.iter().map(|line| format!("  {line}")));
+                output.push(format!(".Lcond{n}:"));
+                output.extend(cond_mc.iter().map(|line| format!("  {line}")));
+                output.extend([
+                    "  lw t1,0(sp)".to_owned(),
+                    "  addi sp,sp,8".to_owned(),
+                    format!("  bnez t1,.Lbody{n}"),
+                ]);
+                output.push(format!(".Lend{n}:"));
+
+                Ok(output)
+            }
+            parser::Stmt::Return(e) => {
+                let mut output = vec![
+                    self.gen_converted(e)?
+                        .iter()
+                        .map(|line| format!("    {line}"))
+                        .collect::<Vec<_>>()
+                        .join("\n"),
+                    "# return expr".to_owned(),
+                    "lw a0,0(sp)".to_owned(),
+                    "addi sp,sp,8".to_owned(),
+                ];
+                output.extend(epilogue());
+
+                Ok(output)
+            }
+            parser::Stmt::Expr(e) => {
+                let mut output = self.gen_expr(e)?;
+                output.push("# discard the value".to_owned());
+                output.push("addi sp,sp,8".to_owned());
+
+                Ok(output)
+            }
+            parser::Stmt::Break => match self.breaks.last() {
+                Some(label) => Ok(vec![format!("j {label}")]),
+                None => Err(GenError::BreakOutside),
+            },
+            parser::Stmt::Continue => match self.continues.last() {
+                Some(label) => Ok(vec![format!("j {label}")]),
+                None => Err(GenError::ContinueOutside),
+            },
+            parser::Stmt::Switch { cond, body } => self.gen_switch(*cond, *body),
+            parser::Stmt::Case { value, stmt } => {
+                let value = const_eval(&value)?;
+                let label = format!(".Lcase{}", self.label());
+                let switch = self.switches.last_mut().ok_or(GenError::CaseOutside)?;
+                if switch.cases.iter().any(|(v, _)| *v == value) {
+                    return Err(GenError::DuplicateCase { value });
+                }
+                switch.cases.push((value, label.clone()));
+
+                let mut output = vec![format!("{label}:")];
+                output.extend(self.gen_stmt(*stmt)?);
+                Ok(output)
+            }
+            parser::Stmt::Default(stmt) => {
+                let label = format!(".Ldefault{}", self.label());
+                let switch = self.switches.last_mut().ok_or(GenError::DefaultOutside)?;
+                if switch.default.is_some() {
+                    return Err(GenError::DuplicateDefault);
+                }
+                switch.default = Some(label.clone());
+
+                let mut output = vec![format!("{label}:")];
+                output.extend(self.gen_stmt(*stmt)?);
+                Ok(output)
+            }
+            parser::Stmt::Block(stmts) => self.gen_block(stmts),
+            parser::Stmt::If { cond, then } => {
+                let n = self.label();
+                let cond_mc = self.gen_expr(*cond)?;
+                let then_mc = self.gen_block(vec![*then])?;
+
+                let mut output = cond_mc;
+                output.extend([
+                    "lw t1,0(sp)".to_owned(),
+                    "addi sp,sp,8".to_owned(),
+                    format!("beqz t1,.Lend{n}"),
+                ]);
+                output.extend(then_mc.iter().map(|line| format!("  {line}")));
+                output.push(format!(".Lend{n}:"));
+
+                Ok(output)
+            }
+            parser::Stmt::IfEls { cond, then, els } => {
+                let n = self.label();
+                let cond_mc = self.gen_expr(*cond)?;
+                let then_mc = self.gen_block(vec![*then])?;
+                let els_mc = self.gen_block(vec![*els])?;
+
+                // any value but 0 is true
+                let mut output = cond_mc;
+                output.extend([
+                    "lw t1,0(sp)".to_owned(),
+                    "addi sp,sp,8".to_owned(),
+                    format!("beqz t1,.Lels{n}"),
+                ]);
+                output.extend(then_mc.iter().map(|line| format!("  {line}")));
+                output.push(format!("  j .Lend{n}"));
+                output.push(format!(".Lels{n}:"));
+                output.extend(els_mc.iter().map(|line| format!("  {line}")));
+                output.push(format!(".Lend{n}:"));
+
+                Ok(output)
+            }
+        }
+    }
+
+    fn gen_for(
+        &mut self,
+        asnmt: Option<Box<parser::Asnmt>>,
+        cond: Option<Box<parser::Expr>>,
+        update: Option<Box<parser::Asnmt>>,
+        body: parser::Stmt,
+    ) -> Result<Vec<String>, GenError> {
+        let n = self.label();
+        let mut output = match asnmt {
+            Some(a) => self.gen_asnmt(*a)?,
+            None => Vec::new(),
+        };
+
+        output.push(format!(".Lcond{n}:"));
+        if let Some(cond) = cond {
+            let cond_mc = self.gen_expr(*cond)?;
+            output.extend(cond_mc.iter().map(|line| format!("  {line}")));
+            output.extend([
                 "  lw t1,0(sp)".to_owned(),
                 "  addi sp,sp,8".to_owned(),
-                "  li t2,1".to_owned(),
-                "  beq t1,t2,then".to_owned(),
-                "  bne t1,t2,els".to_owned(),
-                "################################### .then ###################################"
-                    .to_owned(),
-                "then:".to_owned(),
-                then_mc
-                    .iter()
-                    .map(|line| format!("  {line}"))
-                    .chain(std::iter::once("  j end".to_owned()))
-                    .collect::<Vec<_>>()
-                    .join("\n"),
-                "################################### .els ###################################"
-                    .to_owned(),
-                "els:".to_owned(),
-                els_mc
-                    .iter()
-                    .map(|line| format!("  {line}"))
-                    .chain(std::iter::once("  j end".to_owned()))
-                    .collect::<Vec<_>>()
-                    .join("\n"),
-                "################################### .end ###################################"
-                    .to_owned(),
-                "end:".to_owned(),
-                "  ret".to_owned(),
-            ];
-
-            output
+                format!("  beqz t1,.Lend{n}"),
+            ]);
+        }
+        let body_mc = self.gen_loop_body(body, format!(".Lend{n}"), format!(".Lstep{n}"))?;
+        output.extend(body_mc.iter().map(|line| format!("  {line}")));
+        output.push(format!(".Lstep{n}:"));
+        if let Some(update) = update {
+            let update_mc = self.gen_asnmt(*update)?;
+            output.extend(update_mc.iter().map(|line| format!("  {line}")));
         }
+        output.push(format!("  j .Lcond{n}"));
+        output.push(format!(".Lend{n}:"));
+
+        Ok(output)
     }
-}
 
-fn gen_expr(e: parser::Expr) -> Vec<String> {
-    match e {
-        parser::Expr::Var(id) => {
-            let offset = calc_offset(&id);
-            vec![
-                "# elimination of variable".to_owned(),
-                format!("lw t0,{offset}(fp)"),
-                "sw t0,0(sp)".to_owned(),
-            ]
+    fn gen_switch(
+        &mut self,
+        cond: parser::Expr,
+        body: parser::Stmt,
+    ) -> Result<Vec<String>, GenError> {
+        let n = self.label();
+        let cond_mc = self.gen_expr(cond)?;
+
+        // the cases are only known once the body is generated, and control
+        // falls through from one to the next as it would from any label
+        self.switches.push(Switch::default());
+        self.breaks.push(format!(".Lend{n}"));
+        let body_mc = self.gen_block(vec![body]);
+        self.breaks.pop();
+        let switch = self.switches.pop().unwrap();
+        let body_mc = body_mc?;
+
+        let mut cases = switch.cases;
+        cases.sort();
+        let default = switch.default.unwrap_or(format!(".Lend{n}"));
+
+        let mut output = cond_mc;
+        output.extend(["lw t0,0(sp)".to_owned(), "addi sp,sp,8".to_owned()]);
+        let spread = match (cases.first(), cases.last()) {
+            (Some((min, _)), Some((max, _))) => *max as i64 - *min as i64 + 1,
+            _ => 0,
+        };
+        if cases.len() >= TABLE_MIN_CASES && spread <= TABLE_SPREAD * cases.len() as i64 {
+            output.extend(jump_table(n, &cases, spread, &default));
+        } else {
+            output.extend(self.decision_tree(&cases, &default));
         }
-        parser::Expr::Int(n) => {
+        output.extend(body_mc.iter().map(|line| format!("  {line}")));
+        output.push(format!(".Lend{n}:"));
+
+        Ok(output)
+    }
+
+    // the value being switched on is in t0
+    fn decision_tree(&mut self, cases: &[(i32, String)], default: &str) -> Vec<String> {
+        if cases.len() < TREE_MIN_CASES {
             let mut output = Vec::new();
-            output.push("# 1. load".to_owned());
-            output.push(format!("li t1,{n}"));
-            output.push("".to_owned());
-
-            output.push("# 2. push".to_owned());
-            output.push("addi sp,sp,-8".to_owned());
-            output.push("sw t1,0(sp)".to_owned()); // i128?
-            output.push(
-                "#----------------------------------------------------------------------------"
-                    .to_owned(),
-            );
+            for (value, label) in cases {
+                output.push(format!("li t1,{value}"));
+                output.push(format!("beq t0,t1,{label}"));
+            }
+            output.push(format!("j {default}"));
+            return output;
+        }
+
+        let mid = cases.len() / 2;
+        let (value, label) = &cases[mid];
+        let low = format!(".Llow{}", self.label());
+        let mut output = vec![
+            format!("li t1,{value}"),
+            format!("beq t0,t1,{label}"),
+            format!("blt t0,t1,{low}"),
+        ];
+        output.extend(self.decision_tree(&cases[mid + 1..], default));
+        output.push(format!("{low}:"));
+        output.extend(self.decision_tree(&cases[..mid], default));
 
-            output
+        output
+    }
+
+    // t3 = t2 op t1
+    fn gen_binop(&self, op: parser::BinOp) -> String {
+        match op {
+            parser::BinOp::Add => "add t3,t2,t1".to_owned(),
+            parser::BinOp::Sub => "sub t3,t2,t1".to_owned(),
+            parser::BinOp::Mult if self.target.mul => "mul t3,t2,t1".to_owned(),
+            parser::BinOp::Div if self.target.mul => "div t3,t2,t1".to_owned(),
+            parser::BinOp::Mod if self.target.mul => "rem t3,t2,t1".to_owned(),
+            // without the M extension libgcc does it, called the same way as
+            // any function
+            parser::BinOp::Mult => libcall("__mulsi3"),
+            parser::BinOp::Div => libcall("__divsi3"),
+            parser::BinOp::Mod => libcall("__modsi3"),
         }
-        parser::Expr::Str(_) => todo!(),
-        parser::Expr::UnaryE { op, l } => todo!(),
-        parser::Expr::BinE { op, l, r } => {
-            let left_expr = gen_expr(*l);
-            let right_expr = gen_expr(*r);
-
-            let mut output = Vec::with_capacity(left_expr.len() + right_expr.len() + 8);
-            output.extend(left_expr);
-            output.extend(right_expr);
-
-            // emulating stack machine's push/pop 1AC with register machine's load/store 3AC
-            // 1. pop the operands
-            output.push("# 1. pop the operands".to_owned());
-            output.push("lw t1,0(sp)".to_owned());
-            output.push("addi sp,sp,8".to_owned());
-            output.push("lw t2,0(sp)".to_owned());
-            output.push("addi sp,sp,8".to_owned());
-            output.push("".to_owned());
-
-            // 2. operate on the operands
-            let instr = match op {
-                parser::BinOp::Add => "add t3,t2,t1".to_owned(),
-                parser::BinOp::Sub => "sub t3,t2,t1".to_owned(),
-                parser::BinOp::Mult => "mul t3,t2,t1".to_owned(),
-                parser::BinOp::Div => "div t3,t2,t1".to_owned(),
-                parser::BinOp::Mod => todo!(),
+    }
+
+    // everything on the stack is an int, so a floating constant is loaded
+    // from .rodata and converted on the way, truncating like a C cast does
+    fn gen_float(&mut self, bits: u64, width: u32) -> Vec<String> {
+        let n = self.label();
+        let mut output = vec![
+            format!("# {width}-bit floating constant"),
+            ".section .rodata".to_owned(),
+            format!(".align {}", (width / 8).ilog2()),
+            format!(".Lfloat{n}:"),
+            match width {
+                32 => format!(".word {bits:#x}"),
+                _ => format!(".dword {bits:#x}"),
+            },
+            ".text".to_owned(),
+            format!("la t1,.Lfloat{n}"),
+        ];
+        if self.target.flen >= width {
+            let (load, fmt) = match width {
+                32 => ("flw", "s"),
+                _ => ("fld", "d"),
             };
-            output.push("# 2. operate on the operands".to_owned());
-            output.push(instr);
-            output.push("".to_owned());
-
-            // 3. push the value
-            output.push("# 3. push the value".to_owned());
-            output.push("addi sp,sp,-8".to_owned());
-            output.push("sw t3,0(sp)".to_owned());
-            output.push(
-                "#----------------------------------------------------------------------------"
-                    .to_owned(),
-            );
+            output.push(format!("{load} ft0,0(t1)"));
+            output.push(format!("fcvt.w.{fmt} t1,ft0,rtz"));
+        } else {
+            // libgcc takes the bits in the integer registers, a double in two
+            output.push("lw a0,0(t1)".to_owned());
+            if width == 64 {
+                output.push("lw a1,4(t1)".to_owned());
+            }
+            output.push("mv s1,sp".to_owned());
+            output.push("andi sp,sp,-16".to_owned());
+            output.push(match width {
+                32 => "call __fixsfsi".to_owned(),
+                _ => "call __fixdfsi".to_owned(),
+            });
+            output.push("mv sp,s1".to_owned());
+            output.push("mv t1,a0".to_owned());
+        }
+        output.push("addi sp,sp,-8".to_owned());
+        output.push("sw t1,0(sp)".to_owned());
 
-            output
+        output
+    }
+
+    // an expression whose value is converted to int, where a floating
+    // constant expression is folded and converted once, as a whole
+    fn gen_converted(&mut self, e: parser::Expr) -> Result<Vec<String>, GenError> {
+        if !is_floating(&e) {
+            return self.gen_expr(e);
+        }
+        match const_float(&e) {
+            Some((x, 32)) => Ok(self.gen_float((x as f32).to_bits() as u64, 32)),
+            Some((x, _)) => Ok(self.gen_float(x.to_bits(), 64)),
+            None => Err(GenError::FloatOperand),
         }
-        parser::Expr::RelE { op, l, r } => {
-            let left_expr = gen_expr(*l);
-            let right_expr = gen_expr(*r);
-
-            let mut output = Vec::with_capacity(left_expr.len() + right_expr.len() + 8);
-            output.extend(left_expr);
-            output.extend(right_expr);
-
-            // emulating stack machine's push/pop 1AC with register machine's load/store 3AC
-            output.push("# 1. (t2, t1) <- pop".to_owned());
-            output.push("lw t1,0(sp)".to_owned());
-            output.push("addi sp,sp,8".to_owned());
-            output.push("lw t2,0(sp)".to_owned());
-            output.push("addi sp,sp,8".to_owned());
-            output.push("".to_owned());
-
-            // 2. operate on the operands
-            let instr = match op {
-                parser::RelOp::Eq => {
-                    vec!["sub t3,t2,t1".to_owned(), "seqz t3,t3".to_owned()].join("\n")
+    }
+
+    fn gen_expr(&mut self, e: parser::Expr) -> Result<Vec<String>, GenError> {
+        match e {
+            parser::Expr::Var(id) => {
+                let offset = self.lookup(&id)?;
+                Ok(vec![
+                    "# elimination of variable".to_owned(),
+                    format!("lw t0,{offset}(fp)"),
+                    "addi sp,sp,-8".to_owned(),
+                    "sw t0,0(sp)".to_owned(),
+                ])
+            }
+            parser::Expr::Call { id, args } => {
+                let n = args.len();
+                let mut output = Vec::new();
+                for arg in args {
+                    output.extend(self.gen_converted(arg)?);
                 }
-                parser::RelOp::Neq => vec![
-                    "sub t3,t2,t1".to_owned(),
-                    "seqz t3,t3".to_owned(),
-                    "xori t3,t3,1".to_owned(),
-                ]
-                .join("\n"),
-                parser::RelOp::And => "and t3,t2,t1".to_owned(), // TODO: does riscv short circuit?
-                parser::RelOp::Or => "or t3,t2,t1".to_owned(),   // TODO: does riscv short circuit?
-                parser::RelOp::LtEq => vec![
-                    // a <= b equivalent to !(b < a)
-                    "slt t3,t1,t2".to_owned(),   // b < a
-                    "  xori t3,t3,1".to_owned(), // !(b < a)
-                ]
-                .join("\n"),
-                parser::RelOp::Lt => "slt t3,t2,t1".to_owned(),
-                parser::RelOp::GtEq => vec![
-                    // a >= b equivalent b <= a equivalent to !(a < b)
-                    "slt t3,t2,t1".to_owned(),   // a < b
-                    "  xori t3,t3,1".to_owned(), // !(a < b)
-                ]
-                .join("\n"),
-                parser::RelOp::Gt => "slt t3,t1,t2".to_owned(),
-            };
-            output.push("# 2. op(t2, t1)".to_owned());
-            output.push(instr);
-            output.push("".to_owned());
-
-            // 3. push value in t3 onto stack
-            output.push("# 3. push t3 ->".to_owned());
-            output.push("addi sp,sp,-8".to_owned());
-            output.push("sw t3,0(sp)".to_owned());
-            output.push(
-                "#----------------------------------------------------------------------------"
-                    .to_owned(),
-            );
 
-            output
+                // the arguments were pushed in order, so the last is on top
+                output.push(format!("# call {}", id.0));
+                for i in 0..n.min(ARG_REGS) {
+                    output.push(format!("lw a{i},{}(sp)", 8 * (n - 1 - i)));
+                }
+
+                // the rest go below a 16 byte aligned sp, which s1 keeps
+                // the old value of since the callee has to preserve it
+                let spilled = n.saturating_sub(ARG_REGS);
+                output.push("mv s1,sp".to_owned());
+                if spilled > 0 {
+                    output.push(format!("addi sp,sp,-{}", 4 * spilled));
+                }
+                output.push("andi sp,sp,-16".to_owned());
+                for j in 0..spilled {
+                    output.push(format!("lw t0,{}(s1)", 8 * (n - 1 - ARG_REGS - j)));
+                    output.push(format!("sw t0,{}(sp)", 4 * j));
+                }
+                output.push(format!("call {}", id.0));
+                output.push("mv sp,s1".to_owned());
+
+                // the value replaces the arguments on the stack
+                output.push(format!("addi sp,sp,{}", 8 * n as i32 - 8));
+                output.push("sw a0,0(sp)".to_owned());
+                output.push(
+                    "#----------------------------------------------------------------------------"
+                        .to_owned(),
+                );
+
+                Ok(output)
+            }
+            parser::Expr::Char(c) => self.gen_expr(parser::Expr::Int(c as i128)),
+            parser::Expr::Int(n)
+            | parser::Expr::UInt(n)
+            | parser::Expr::Long(n)
+            | parser::Expr::ULong(n) => {
+                let mut output = Vec::new();
+                output.push("# 1. load".to_owned());
+                output.push(format!("li t1,{n}"));
+                output.push("".to_owned());
+
+                output.push("# 2. push".to_owned());
+                output.push("addi sp,sp,-8".to_owned());
+                output.push("sw t1,0(sp)".to_owned()); // i128?
+                output.push(
+                    "#----------------------------------------------------------------------------"
+                        .to_owned(),
+                );
+
+                Ok(output)
+            }
+            parser::Expr::Str(bytes) => {
+                // the bytes go in .rodata, NUL terminated, and the string is
+                // the address of the first
+                let n = self.label();
+                let bytes = bytes
+                    .iter()
+                    .chain([&0])
+                    .map(|b| b.to_string())
+                    .collect::<Vec<_>>();
+                Ok(vec![
+                    "# string literal".to_owned(),
+                    ".section .rodata".to_owned(),
+                    format!(".Lstr{n}:"),
+                    format!(".byte {}", bytes.join(",")),
+                    ".text".to_owned(),
+                    format!("la t1,.Lstr{n}"),
+                    "addi sp,sp,-8".to_owned(),
+                    "sw t1,0(sp)".to_owned(),
+                ])
+            }
+            // only gen_converted takes floating constants
+            parser::Expr::Float(_) | parser::Expr::Double(_) | parser::Expr::LongDouble(_) => {
+                Err(GenError::FloatOperand)
+            }
+            parser::Expr::UnaryE { op, l } => {
+                let mut output = self.gen_expr(*l)?;
+                // +x is just x, and -x is 0 - x
+                if op == parser::UnaryOp::Sub {
+                    output.push("# negate".to_owned());
+                    output.push("lw t1,0(sp)".to_owned());
+                    output.push("neg t1,t1".to_owned());
+                    output.push("sw t1,0(sp)".to_owned());
+                }
+
+                Ok(output)
+            }
+            parser::Expr::BinE { op, l, r } => {
+                let left_expr = self.gen_expr(*l)?;
+                let right_expr = self.gen_expr(*r)?;
+
+                let mut output = Vec::with_capacity(left_expr.len() + right_expr.len() + 8);
+                output.extend(left_expr);
+                output.extend(right_expr);
+
+                // emulating stack machine's push/pop 1AC with register machine's load/store 3AC
+                // 1. pop the operands
+                output.push("# 1. pop the operands".to_owned());
+                output.push("lw t1,0(sp)".to_owned());
+                output.push("addi sp,sp,8".to_owned());
+                output.push("lw t2,0(sp)".to_owned());
+                output.push("addi sp,sp,8".to_owned());
+                output.push("".to_owned());
+
+                // 2. operate on the operands
+                output.push("# 2. operate on the operands".to_owned());
+                output.push(self.gen_binop(op));
+                output.push("".to_owned());
+
+                // 3. push the value
+                output.push("# 3. push the value".to_owned());
+                output.push("addi sp,sp,-8".to_owned());
+                output.push("sw t3,0(sp)".to_owned());
+                output.push(
+                    "#----------------------------------------------------------------------------"
+                        .to_owned(),
+                );
+
+                Ok(output)
+            }
+            parser::Expr::RelE { op, l, r } => {
+                let left_expr = self.gen_expr(*l)?;
+                let right_expr = self.gen_expr(*r)?;
+
+                let mut output = Vec::with_capacity(left_expr.len() + right_expr.len() + 8);
+                output.extend(left_expr);
+                output.extend(right_expr);
+
+                // emulating stack machine's push/pop 1AC with register machine's load/store 3AC
+                output.push("# 1. (t2, t1) <- pop".to_owned());
+                output.push("lw t1,0(sp)".to_owned());
+                output.push("addi sp,sp,8".to_owned());
+                output.push("lw t2,0(sp)".to_owned());
+                output.push("addi sp,sp,8".to_owned());
+                output.push("".to_owned());
+
+                // 2. operate on the operands
+                let instr = match op {
+                    parser::RelOp::Eq => {
+                        ["sub t3,t2,t1".to_owned(), "seqz t3,t3".to_owned()].join("\n")
+                    }
+                    parser::RelOp::Neq => [
+                        "sub t3,t2,t1".to_owned(),
+                        "seqz t3,t3".to_owned(),
+                        "xori t3,t3,1".to_owned(),
+                    ]
+                    .join("\n"),
+                    parser::RelOp::And => "and t3,t2,t1".to_owned(), // TODO: does riscv short circuit?
+                    parser::RelOp::Or => "or t3,t2,t1".to_owned(), // TODO: does riscv short circuit?
+                    parser::RelOp::LtEq => [
+                        // a <= b equivalent to !(b < a)
+                        "slt t3,t1,t2".to_owned(),   // b < a
+                        "  xori t3,t3,1".to_owned(), // !(b < a)
+                    ]
+                    .join("\n"),
+                    parser::RelOp::Lt => "slt t3,t2,t1".to_owned(),
+                    parser::RelOp::GtEq => [
+                        // a >= b equivalent b <= a equivalent to !(a < b)
+                        "slt t3,t2,t1".to_owned(),   // a < b
+                        "  xori t3,t3,1".to_owned(), // !(a < b)
+                    ]
+                    .join("\n"),
+                    parser::RelOp::Gt => "slt t3,t1,t2".to_owned(),
+                };
+                output.push("# 2. op(t2, t1)".to_owned());
+                output.push(instr);
+                output.push("".to_owned());
+
+                // 3. push value in t3 onto stack
+                output.push("# 3. push t3 ->".to_owned());
+                output.push("addi sp,sp,-8".to_owned());
+                output.push("sw t3,0(sp)".to_owned());
+                output.push(
+                    "#----------------------------------------------------------------------------"
+                        .to_owned(),
+                );
+
+                Ok(output)
+            }
+            parser::Expr::BitE { .. } => todo!(),
+            parser::Expr::LogE { .. } => todo!(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test_illegal {
+    use crate::{parser, target::Target};
+
+    #[test]
+    fn for_scope() {
+        let input = "int main() { for (int i = 0; i < 2; i++) { } return i; }";
+
+        let tree = parser::parse_str(input).unwrap();
+        let err = super::gen(tree, Target::default()).unwrap_err();
+        assert_eq!(err.to_string(), "'i' undeclared");
+    }
+
+    #[test]
+    fn double_param() {
+        let input = "int f(int a, double b) { return a; } int main() { return f(1, 2); }";
+
+        let tree = parser::parse_str(input).unwrap();
+        let err = super::gen(tree, Target::default()).unwrap_err();
+        assert_eq!(err.to_string(), "parameter 'b' has an unsupported type");
+    }
+
+    #[test]
+    fn float_operand() {
+        for input in [
+            "int main() { int x = 1; return x + 0.5; }",
+            "int main() { return 2.5 > 2; }",
+            "int main() { int x = 1; x += 0.5; return x; }",
+        ] {
+            let tree = parser::parse_str(input).unwrap();
+            let err = super::gen(tree, Target::default()).unwrap_err();
+            assert_eq!(
+                err.to_string(),
+                "floating-point operands are only supported in constants converted to int"
+            );
         }
-        parser::Expr::BitE { op, l, r } => todo!(),
-        parser::Expr::LogE { op, l, r } => todo!(),
+    }
+
+    #[test]
+    fn break_outside() {
+        let input = "int main() { break; return 0; }";
+
+        let tree = parser::parse_str(input).unwrap();
+        let err = super::gen(tree, Target::default()).unwrap_err();
+        assert_eq!(err.to_string(), "break statement not within loop or switch");
+    }
+
+    #[test]
+    fn continue_outside() {
+        let input = "int main() { continue; return 0; }";
+
+        let tree = parser::parse_str(input).unwrap();
+        let err = super::gen(tree, Target::default()).unwrap_err();
+        assert_eq!(err.to_string(), "continue statement not within a loop");
+    }
+
+    #[test]
+    fn duplicate_case() {
+        let input = "int main() { switch (1) { case 2: break; case 1 + 1: break; } return 0; }";
+
+        let tree = parser::parse_str(input).unwrap();
+        let err = super::gen(tree, Target::default()).unwrap_err();
+        assert_eq!(err.to_string(), "duplicate case value 2");
+    }
+
+    #[test]
+    fn duplicate_default() {
+        let input = "int main() { switch (1) { default: break; default: break; } return 0; }";
+
+        let tree = parser::parse_str(input).unwrap();
+        let err = super::gen(tree, Target::default()).unwrap_err();
+        assert_eq!(err.to_string(), "multiple default labels in one switch");
+    }
+
+    #[test]
+    fn case_outside() {
+        let input = "int main() { while (1) { case 1: break; } return 0; }";
+
+        let tree = parser::parse_str(input).unwrap();
+        let err = super::gen(tree, Target::default()).unwrap_err();
+        assert_eq!(err.to_string(), "case label not within a switch statement");
+    }
+
+    #[test]
+    fn case_not_constant() {
+        let input = "int main() { int n = 1; switch (n) { case n: break; } return 0; }";
+
+        let tree = parser::parse_str(input).unwrap();
+        let err = super::gen(tree, Target::default()).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "case label does not reduce to an integer constant"
+        );
+    }
+}
+
+#[cfg(test)]
+mod test_switch {
+    use crate::{parser, target::Target};
+    use std::fs;
+
+    const TEST_DIR: &str = "tests/fixtures/din/legal/control_flow";
+
+    fn gen(file: &str) -> String {
+        let input = fs::read_to_string(format!("{TEST_DIR}/{file}"))
+            .expect("Should have been able to read the file");
+
+        let tree = parser::parse_str(&input).unwrap();
+        super::gen(tree, Target::default()).unwrap().join("\n")
+    }
+
+    #[test]
+    fn compare_chain() {
+        let assembly = gen("switch.c");
+        assert!(!assembly.contains(".Ltable"));
+        assert!(!assembly.contains("blt t0,t1"));
+    }
+
+    #[test]
+    fn jump_table() {
+        let assembly = gen("switch_table.c");
+        let table = assembly
+            .lines()
+            .skip_while(|line| !line.starts_with(".Ltable"))
+            .skip(1)
+            .take_while(|line| line.starts_with(".word"))
+            .collect::<Vec<_>>();
+        assert_eq!(table.len(), 5); // 1 to 5, with 4 going to default
+        assert!(table[3].starts_with(".word .Ldefault"));
+    }
+
+    #[test]
+    fn decision_tree() {
+        let assembly = gen("switch_tree.c");
+        assert!(!assembly.contains(".Ltable"));
+        assert!(assembly.contains("blt t0,t1"));
+        assert!(assembly.contains("li t1,-7"));
+    }
+}
+
+#[cfg(test)]
+mod test_target {
+    use crate::{parser, target::Target};
+
+    fn gen(input: &str, march: &str) -> String {
+        let tree = parser::parse_str(input).unwrap();
+        super::gen(tree, Target::parse(march).unwrap())
+            .unwrap()
+            .join("\n")
+    }
+
+    #[test]
+    fn mul() {
+        let assembly = gen("int main() { return 9 * 10 / 2 % 4; }", "rv32im");
+        assert!(assembly.contains("mul t3,t2,t1"));
+        assert!(assembly.contains("div t3,t2,t1"));
+        assert!(assembly.contains("rem t3,t2,t1"));
+        assert!(!assembly.contains("call __"));
+    }
+
+    #[test]
+    fn no_mul() {
+        let assembly = gen("int main() { return 9 * 10 / 2 % 4; }", "rv32i");
+        assert!(assembly.contains("call __mulsi3"));
+        assert!(assembly.contains("call __divsi3"));
+        assert!(assembly.contains("call __modsi3"));
+        assert!(!assembly.contains("mul t3"));
+        assert!(!assembly.contains("div t3"));
+        assert!(!assembly.contains("rem t3"));
+    }
+
+    #[test]
+    fn no_mul_update() {
+        let input = "int main() { int n = 9; n *= 10; n /= 2; n %= 4; return n; }";
+        let assembly = gen(input, "rv32i");
+        assert!(assembly.contains("call __mulsi3"));
+        assert!(assembly.contains("call __divsi3"));
+        assert!(assembly.contains("call __modsi3"));
+        assert!(!assembly.contains("mul t3"));
+        assert!(!assembly.contains("div t3"));
+        assert!(!assembly.contains("rem t3"));
+    }
+
+    #[test]
+    fn float() {
+        // folded to the double 43.25 before it is truncated
+        let assembly = gen("int main() { return 2.75 + 40.5f; }", "rv32g");
+        assert!(assembly.contains(".dword 0x4045a00000000000"));
+        assert!(assembly.contains("fcvt.w.d t1,ft0,rtz"));
+
+        let assembly = gen("int main() { return 2.75f * -2; }", "rv32imf");
+        assert!(assembly.contains(".word 0xc0b00000"));
+        assert!(assembly.contains("fcvt.w.s t1,ft0,rtz"));
+    }
+
+    #[test]
+    fn no_float() {
+        let assembly = gen("int main() { return 2.75 + 40.5f; }", "rv32im");
+        assert!(assembly.contains("call __fixdfsi"));
+        assert!(!assembly.contains("fcvt"));
+
+        let assembly = gen("int main() { return 40.5f; }", "rv32im");
+        assert!(assembly.contains("call __fixsfsi"));
+    }
+
+    #[test]
+    fn str() {
+        let assembly = gen(r#"int main() { return strlen("hi" "\n"); }"#, "rv32i");
+        let rodata = assembly
+            .lines()
+            .map(str::trim)
+            .skip_while(|line| !line.starts_with(".Lstr"))
+            .take(2)
+            .collect::<Vec<_>>();
+        assert_eq!(rodata, [".Lstr1:", ".byte 104,105,10,0"]);
+        assert!(assembly.contains("la t1,.Lstr1"));
     }
 }
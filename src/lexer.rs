@@ -1,4 +1,11 @@
+use crate::{
+    source::{FileId, SourceMap, Span},
+    splice::{splice, Spliced},
+    symbol::Symbol,
+};
 use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use thiserror::Error;
 
 // non-tokens:
 // - comments
@@ -12,684 +19,2670 @@ use serde::{Deserialize, Serialize};
 #[derive(Copy, Clone, PartialEq, Serialize, Deserialize, Debug)]
 pub enum TokenType {
     // introductions (values)
-    LiteralInt, // RE: [0-9]+
-    Identifier, // RE: [a−zA−Z][a−zA−Z0−9]*
+    LiteralInt,   // RE: ([1-9][0-9]*|0[0-7]*|0[xX][0-9a-fA-F]+)([uU][lL]?|[lL][uU]?)?
+    LiteralChar,  // RE: '([^'\\\n]|\\.)+'
+    LiteralStr,   // RE: "([^"\\\n]|\\.)*"
+    LiteralFloat, // RE: ([0-9]*\.[0-9]+|[0-9]+\.?)([eE][+-]?[0-9]+)?[fFlL]?
+    Identifier,   // RE: [a−zA−Z_][a−zA−Z0−9_]*
 
     // keywords (subset of identifiers)
     KeywordInt,
-    KeywordMain,
     KeywordVoid,
     KeywordRet,
     KeywordIf,
     KeywordEls,
     KeywordFor,
+    KeywordWhile,
+    KeywordDo,
+    KeywordBreak,
+    KeywordContinue,
+    KeywordSwitch,
+    KeywordCase,
+    KeywordDefault,
+    KeywordGoto,
+    KeywordChar,
+    KeywordShort,
+    KeywordLong,
+    KeywordFloat,
+    KeywordDouble,
+    KeywordSigned,
+    KeywordUnsigned,
+    KeywordStruct,
+    KeywordUnion,
+    KeywordEnum,
+    KeywordTypedef,
+    KeywordSizeof,
+    KeywordAuto,
+    KeywordRegister,
+    KeywordStatic,
+    KeywordExtern,
+    KeywordConst,
+    KeywordVolatile,
 
     // eliminations (operations)
     Plus,
     Minus,
     Star,
     Slash,
+    Percent,
     LeftAngleBracket,
     RightAngleBracket,
     Equals,
     Bang,
     Amp,
     Bar,
+    Caret,
+    Tilde,
+    Question,
+    Dot,
+    Arrow,
+
+    // multi-character operators, scanned by maximal munch
+    PlusPlus,
+    MinusMinus,
+    DoubleLeftAngleBracket,
+    DoubleRightAngleBracket,
+    LeftAngleBracketEquals,
+    RightAngleBracketEquals,
+    EqualsEquals,
+    BangEquals,
+    AmpAmp,
+    BarBar,
+
+    // compound assignment
+    PlusEquals,
+    MinusEquals,
+    StarEquals,
+    SlashEquals,
+    PercentEquals,
+    DoubleLeftAngleBracketEquals,
+    DoubleRightAngleBracketEquals,
+    AmpEquals,
+    BarEquals,
+    CaretEquals,
 
     // punctuation
     PuncLeftParen,
     PuncRightParen,
     PuncLeftBrace,
     PuncRightBrace,
+    PuncLeftBracket,
+    PuncRightBracket,
     PuncSemiColon,
+    PuncColon,
+    PuncComma,
+    PuncEllipsis,
+
+    // preprocessing
+    Hash,
+    HashHash,
+    Pragma, // a #pragma passed on by the preprocessor, never lexed
+
+    // trivia, only produced when the lexer is asked to keep them
+    Comment,
+    Newline,
 }
 
-#[derive(Clone, PartialEq, Serialize, Deserialize, Debug)]
+/// What a token spells. Identifiers and keywords are interned, so that
+/// names compare in O(1); anything else is read back from the span it was
+/// spelled at, which for a token from a macro body isn't the token's span.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Lexeme {
+    Symbol(Symbol),
+    Spelled(Span),
+}
+
+/// Tokens are small and Copy: they point back into the source buffer
+/// rather than owning their text.
+#[derive(Copy, Clone, PartialEq, Debug)]
 pub struct Token {
-    pub lexeme: String,
+    pub lexeme: Lexeme,
     pub typ: TokenType,
+    pub span: Span,
 }
 
-// TODO: keep track of file and (col, row) for error reporting
-// struct Position {}
+impl Token {
+    /// The name of an identifier or keyword.
+    pub fn symbol(&self) -> Option<Symbol> {
+        match self.lexeme {
+            Lexeme::Symbol(s) => Some(s),
+            Lexeme::Spelled(_) => None,
+        }
+    }
 
-// TODO: just filter out whitespace instead of having a helper function
-pub fn lex(input: &[char]) -> Vec<Token> {
-    let cs = skip_whitespace(input);
+    pub fn text<'a>(&self, source_map: &'a SourceMap) -> Cow<'a, str> {
+        match self.lexeme {
+            Lexeme::Symbol(s) => Cow::Borrowed(s.as_str()),
+            Lexeme::Spelled(span) => source_map.spelling(span),
+        }
+    }
 
-    // literals and identifiers have arbitrary length
-    // operations and punctuations are single ASCII characters
-    match cs {
-        [] => vec![],
-        [f, r @ ..] => match f {
-            '0'..='9' => scan_int(cs),
-            'a'..='z' | 'A'..='Z' => scan_id(cs),
-            '+' => {
-                let t = Token {
-                    lexeme: String::from("+"),
-                    typ: TokenType::Plus,
-                };
+    /// Checks that a constant or literal lexed as a preprocessing token has
+    /// a value, which is when a preprocessing token becomes a token.
+    pub fn check(&self, source_map: &SourceMap) -> Result<(), LexError> {
+        check(*self, &self.text(source_map))
+    }
+}
 
-                std::iter::once(t).chain(lex(r)).collect()
-            }
-            '-' => {
-                let t = Token {
-                    lexeme: String::from("-"),
-                    typ: TokenType::Minus,
-                };
+// the value checks of a token spelled lexeme
+fn check(t: Token, lexeme: &str) -> Result<(), LexError> {
+    match t.typ {
+        TokenType::LiteralInt => int_literal(lexeme, t.span).map(drop),
+        TokenType::LiteralFloat => float_literal(lexeme, t.span).map(drop),
+        TokenType::LiteralChar => char_literal(lexeme, t.span).map(drop),
+        TokenType::LiteralStr => str_literal(lexeme, t.span).map(drop),
+        _ => Ok(()),
+    }
+}
 
-                std::iter::once(t).chain(lex(r)).collect()
-            }
-            '*' => {
-                let t = Token {
-                    lexeme: String::from("*"),
-                    typ: TokenType::Star,
-                };
+#[derive(Error, Clone, PartialEq, Serialize, Deserialize, Debug)]
+pub enum LexError {
+    #[error("illegal character {c:?} in program")]
+    IllegalChar { c: char, span: Span },
+    #[error("unterminated comment")]
+    UnterminatedComment { span: Span },
+    #[error("invalid suffix {suffix:?} on integer constant")]
+    InvalidIntSuffix { suffix: String, span: Span },
+    #[error("invalid digit {c:?} in octal constant")]
+    InvalidOctalDigit { c: char, span: Span },
+    #[error("integer constant is too large for its type")]
+    IntTooLarge { span: Span },
+    #[error("missing terminating {quote} character")]
+    UnterminatedLiteral { quote: char, span: Span },
+    #[error("empty character constant")]
+    EmptyChar { span: Span },
+    #[error("multi-character character constant")]
+    MultiChar { span: Span },
+    #[error("unknown escape sequence '\\{c}'")]
+    InvalidEscape { c: char, span: Span },
+    #[error("octal escape sequence out of range")]
+    OctalEscapeOutOfRange { span: Span },
+    #[error("hex escape sequence out of range")]
+    HexEscapeOutOfRange { span: Span },
+    #[error("invalid suffix {suffix:?} on floating constant")]
+    InvalidFloatSuffix { suffix: String, span: Span },
+    #[error("exponent has no digits")]
+    MalformedExponent { span: Span },
+    #[error("too many decimal points in number")]
+    TooManyDecimalPoints { span: Span },
+    #[error("floating constant exceeds range of its type")]
+    FloatOutOfRange { span: Span },
+}
 
-                std::iter::once(t).chain(lex(r)).collect()
-            }
-            '/' => {
-                let t = Token {
-                    lexeme: String::from("/"),
-                    typ: TokenType::Slash,
-                };
+/// The type of an integer constant. On RV32 (ILP32) int and long are both
+/// 32 bits wide, but they are still distinct types.
+#[derive(Copy, Clone, PartialEq, Eq, Serialize, Deserialize, Debug)]
+pub enum IntType {
+    Int,
+    UnsignedInt,
+    Long,
+    UnsignedLong,
+}
 
-                std::iter::once(t).chain(lex(r)).collect()
-            }
-            '<' => {
-                let t = Token {
-                    lexeme: String::from("<"),
-                    typ: TokenType::LeftAngleBracket,
-                };
+impl IntType {
+    fn max(self) -> u128 {
+        match self {
+            IntType::Int | IntType::Long => i32::MAX as u128,
+            IntType::UnsignedInt | IntType::UnsignedLong => u32::MAX as u128,
+        }
+    }
+}
 
-                std::iter::once(t).chain(lex(r)).collect()
-            }
-            '>' => {
-                let t = Token {
-                    lexeme: String::from(">"),
-                    typ: TokenType::RightAngleBracket,
-                };
+/// Evaluates an integer constant, picking its type by C89 6.1.3.2: the first
+/// type in the list for its radix and suffix which can represent the value.
+pub fn int_literal(lexeme: &str, span: Span) -> Result<(u128, IntType), LexError> {
+    let (val, radix, suffix) = int_parts(lexeme, span)?;
+
+    #[rustfmt::skip]
+    let candidates: &[IntType] = match (radix, suffix.as_str()) {
+        (10, "") => &[IntType::Int, IntType::Long, IntType::UnsignedLong],
+        (_, "") => &[IntType::Int, IntType::UnsignedInt, IntType::Long, IntType::UnsignedLong],
+        (_, "u") => &[IntType::UnsignedInt, IntType::UnsignedLong],
+        (_, "l") => &[IntType::Long, IntType::UnsignedLong],
+        _ => &[IntType::UnsignedLong],
+    };
+
+    #[rustfmt::skip]
+    let typ = candidates
+        .iter()
+        .find(|t| val <= t.max())
+        .ok_or(LexError::IntTooLarge { span })?;
+
+    Ok((val, *typ))
+}
 
-                std::iter::once(t).chain(lex(r)).collect()
-            }
-            '=' => {
-                let t = Token {
-                    lexeme: String::from("="),
-                    typ: TokenType::Equals,
-                };
+/// Evaluates an integer constant in an #if, where it has the type intmax_t
+/// or uintmax_t (C99 6.10.1), which are 64 bits as in gcc. Returns whether
+/// it is unsigned, which it is with a u suffix or when it doesn't fit
+/// intmax_t.
+pub fn pp_int_literal(lexeme: &str, span: Span) -> Result<(u64, bool), LexError> {
+    let (val, _, suffix) = int_parts(lexeme, span)?;
+    let val = u64::try_from(val).map_err(|_| LexError::IntTooLarge { span })?;
+    Ok((val, suffix.contains('u') || val > i64::MAX as u64))
+}
 
-                std::iter::once(t).chain(lex(r)).collect()
-            }
-            '!' => {
-                let t = Token {
-                    lexeme: String::from("!"),
-                    typ: TokenType::Bang,
-                };
+// the value, radix and lowercased suffix of an integer constant
+fn int_parts(lexeme: &str, span: Span) -> Result<(u128, u32, String), LexError> {
+    let (radix, digits) = match lexeme.as_bytes() {
+        [b'0', b'x' | b'X', ..] => (16, &lexeme[2..]),
+        [b'0', ..] => (8, lexeme),
+        _ => (10, lexeme),
+    };
+
+    let end = digits
+        .find(|c: char| !c.is_ascii_hexdigit() || (radix != 16 && c.is_ascii_alphabetic()))
+        .unwrap_or(digits.len());
+    let (digits, suffix) = digits.split_at(end);
+    if radix == 16 && digits.is_empty() {
+        return Err(LexError::InvalidIntSuffix {
+            suffix: lexeme[1..].to_owned(),
+            span,
+        });
+    }
 
-                std::iter::once(t).chain(lex(r)).collect()
-            }
-            '&' => {
-                let t = Token {
-                    lexeme: String::from("&"),
-                    typ: TokenType::Amp,
-                };
+    let lower = suffix.to_ascii_lowercase();
+    if !matches!(lower.as_str(), "" | "u" | "l" | "ul" | "lu") {
+        return Err(LexError::InvalidIntSuffix {
+            suffix: suffix.to_owned(),
+            span,
+        });
+    }
 
-                std::iter::once(t).chain(lex(r)).collect()
-            }
-            '|' => {
-                let t = Token {
-                    lexeme: String::from("|"),
-                    typ: TokenType::Bar,
-                };
+    let mut val: u128 = 0;
+    for c in digits.chars() {
+        let d = c
+            .to_digit(radix)
+            .ok_or(LexError::InvalidOctalDigit { c, span })?;
+        val = val
+            .checked_mul(radix as u128)
+            .and_then(|v| v.checked_add(d as u128))
+            .ok_or(LexError::IntTooLarge { span })?;
+    }
 
-                std::iter::once(t).chain(lex(r)).collect()
-            }
-            '(' => {
-                let t = Token {
-                    lexeme: String::from("("),
-                    typ: TokenType::PuncLeftParen,
-                };
+    Ok((val, radix, lower))
+}
 
-                std::iter::once(t).chain(lex(r)).collect()
-            }
-            ')' => {
-                let t = Token {
-                    lexeme: String::from(")"),
-                    typ: TokenType::PuncRightParen,
-                };
+/// The type of a floating constant, given by its suffix.
+#[derive(Copy, Clone, PartialEq, Eq, Serialize, Deserialize, Debug)]
+pub enum FloatType {
+    Float,
+    Double,
+    LongDouble,
+}
 
-                std::iter::once(t).chain(lex(r)).collect()
-            }
-            '{' => {
-                let t = Token {
-                    lexeme: String::from("{"),
-                    typ: TokenType::PuncLeftBrace,
-                };
+/// Evaluates a floating constant to the nearest double (rounded once more to
+/// float for an f suffix). long double is 128 bits on RV32, but din only
+/// carries it at double precision.
+pub fn float_literal(lexeme: &str, span: Span) -> Result<(f64, FloatType), LexError> {
+    let (body, typ) = match lexeme.as_bytes().last() {
+        Some(b'f' | b'F') => (&lexeme[..lexeme.len() - 1], FloatType::Float),
+        Some(b'l' | b'L') => (&lexeme[..lexeme.len() - 1], FloatType::LongDouble),
+        _ => (lexeme, FloatType::Double),
+    };
+
+    // mantissa: digits with at most one dot
+    let mantissa_len = body
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(body.len());
+    let (mantissa, rest) = body.split_at(mantissa_len);
+    if mantissa.matches('.').count() > 1 {
+        return Err(LexError::TooManyDecimalPoints { span });
+    }
 
-                std::iter::once(t).chain(lex(r)).collect()
-            }
-            '}' => {
-                let t = Token {
-                    lexeme: String::from("}"),
-                    typ: TokenType::PuncRightBrace,
-                };
+    // exponent: e, optional sign, then at least one digit
+    if let Some(exp) = rest.strip_prefix(['e', 'E']) {
+        let digits = exp.strip_prefix(['+', '-']).unwrap_or(exp);
+        if digits.is_empty() {
+            return Err(LexError::MalformedExponent { span });
+        }
+        if let Some(i) = digits.find(|c: char| !c.is_ascii_digit()) {
+            return Err(LexError::InvalidFloatSuffix {
+                suffix: lexeme[lexeme.len() - digits.len() + i..].to_owned(),
+                span,
+            });
+        }
+    } else if !rest.is_empty() {
+        return Err(LexError::InvalidFloatSuffix {
+            suffix: lexeme[mantissa_len..].to_owned(),
+            span,
+        });
+    }
 
-                std::iter::once(t).chain(lex(r)).collect()
-            }
-            ';' => {
-                let t = Token {
-                    lexeme: String::from(";"),
-                    typ: TokenType::PuncSemiColon,
-                };
+    let val: f64 = body.parse().unwrap();
+    let in_range = match typ {
+        FloatType::Float => (val as f32).is_finite(),
+        FloatType::Double | FloatType::LongDouble => val.is_finite(),
+    };
+    if !in_range {
+        return Err(LexError::FloatOutOfRange { span });
+    }
 
-                std::iter::once(t).chain(lex(r)).collect()
-            }
-            _ => {
-                let t = Token {
-                    lexeme: String::from("PANIC?"),
-                    typ: TokenType::Plus,
-                };
+    Ok((val, typ))
+}
+
+/// Evaluates a character constant. char is unsigned on RISC-V, so '\xff' is
+/// 255 rather than -1.
+pub fn char_literal(lexeme: &str, span: Span) -> Result<u8, LexError> {
+    match unescape(&lexeme[1..lexeme.len() - 1], span)?.as_slice() {
+        [] => Err(LexError::EmptyChar { span }),
+        [c] => Ok(*c),
+        _ => Err(LexError::MultiChar { span }),
+    }
+}
+
+/// Evaluates a string literal to the bytes it denotes, without the
+/// terminating NUL.
+pub fn str_literal(lexeme: &str, span: Span) -> Result<Vec<u8>, LexError> {
+    unescape(&lexeme[1..lexeme.len() - 1], span)
+}
 
-                std::iter::once(t).chain(lex(r)).collect()
+fn unescape(body: &str, span: Span) -> Result<Vec<u8>, LexError> {
+    let mut bytes = Vec::with_capacity(body.len());
+    let mut cs = body.chars().peekable();
+
+    while let Some(c) = cs.next() {
+        if c != '\\' {
+            bytes.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes());
+            continue;
+        }
+
+        // the lexer never ends a literal on a backslash
+        let e = cs.next().unwrap();
+        let b = match e {
+            '\'' | '"' | '?' | '\\' => e as u8,
+            'a' => 0x07,
+            'b' => 0x08,
+            'f' => 0x0c,
+            'n' => b'\n',
+            'r' => b'\r',
+            't' => b'\t',
+            'v' => 0x0b,
+            '0'..='7' => {
+                // at most three octal digits
+                let mut val = e.to_digit(8).unwrap();
+                for _ in 0..2 {
+                    match cs.peek().and_then(|c| c.to_digit(8)) {
+                        Some(d) => {
+                            val = val * 8 + d;
+                            cs.next();
+                        }
+                        None => break,
+                    }
+                }
+                u8::try_from(val).map_err(|_| LexError::OctalEscapeOutOfRange { span })?
+            }
+            'x' => {
+                // as many hex digits as follow
+                let mut val: u32 = 0;
+                let mut n = 0;
+                while let Some(d) = cs.peek().and_then(|c| c.to_digit(16)) {
+                    val = val.saturating_mul(16).saturating_add(d);
+                    n += 1;
+                    cs.next();
+                }
+                if n == 0 {
+                    return Err(LexError::InvalidEscape { c: 'x', span });
+                }
+                u8::try_from(val).map_err(|_| LexError::HexEscapeOutOfRange { span })?
             }
-        },
+            c => return Err(LexError::InvalidEscape { c, span }),
+        };
+        bytes.push(b);
     }
+
+    Ok(bytes)
 }
 
-fn scan_int(input: &[char]) -> Vec<Token> {
-    // scan_int calls skip_whitespace too to remain idempotent
-    let cs = skip_whitespace(input);
+/// The language standard being lexed. C99 only adds `//` line comments as far
+/// as the lexer is concerned.
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Default, Debug)]
+pub enum Std {
+    #[default]
+    C89,
+    C99,
+}
 
-    match cs {
-        [] => vec![],
-        [f, r @ ..] => match f {
-            '0'..='9' => {
-                #[rustfmt::skip]
-                let i = r
-                    .iter()
-                    .take_while(|&&c| c.is_numeric())
-                    .count();
+impl LexError {
+    pub fn span(&self) -> Span {
+        match self {
+            LexError::IllegalChar { span, .. }
+            | LexError::UnterminatedComment { span }
+            | LexError::InvalidIntSuffix { span, .. }
+            | LexError::InvalidOctalDigit { span, .. }
+            | LexError::IntTooLarge { span }
+            | LexError::UnterminatedLiteral { span, .. }
+            | LexError::EmptyChar { span }
+            | LexError::MultiChar { span }
+            | LexError::InvalidEscape { span, .. }
+            | LexError::OctalEscapeOutOfRange { span }
+            | LexError::HexEscapeOutOfRange { span }
+            | LexError::InvalidFloatSuffix { span, .. }
+            | LexError::MalformedExponent { span }
+            | LexError::TooManyDecimalPoints { span }
+            | LexError::FloatOutOfRange { span } => *span,
+        }
+    }
+}
 
-                #[rustfmt::skip]
-                let f = cs[..=i]
-                    .iter()
-                    .collect::<String>();
-                let new_r = &cs[i + 1..];
+/// A streaming lexer: each call to `next` scans exactly one token (or error)
+/// off the front of the input, so tokens can be consumed lazily and lexing a
+/// file takes linear time and constant stack.
+pub struct Lexer<'a> {
+    input: Spliced<'a>, // after trigraphs and line splicing
+    file: FileId,
+    offset: usize, // the next unscanned byte
+    std: Std,
+    keep_comments: bool,
+    keep_newlines: bool,
+    pp_tokens: bool,
+}
 
-                let t = Token {
-                    lexeme: f,
-                    typ: TokenType::LiteralInt,
-                };
+impl<'a> Lexer<'a> {
+    pub fn new(input: &'a str, file: FileId) -> Self {
+        Lexer {
+            input: splice(input),
+            file,
+            offset: 0,
+            std: Std::default(),
+            keep_comments: false,
+            keep_newlines: false,
+            pp_tokens: false,
+        }
+    }
 
-                std::iter::once(t).chain(lex(new_r)).collect()
+    pub fn std(mut self, std: Std) -> Self {
+        self.std = std;
+        self
+    }
+
+    /// Emit comments as `TokenType::Comment` trivia instead of discarding
+    /// them, for tools that want to round-trip the source.
+    pub fn keep_comments(mut self, keep: bool) -> Self {
+        self.keep_comments = keep;
+        self
+    }
+
+    /// Emit the end of each logical line as `TokenType::Newline`, which the
+    /// preprocessor needs to find the extent of its directives.
+    pub fn keep_newlines(mut self, keep: bool) -> Self {
+        self.keep_newlines = keep;
+        self
+    }
+
+    /// Lex preprocessing tokens: numbers and character and string literals
+    /// are scanned by their shape alone, so that `1.2.3` or `'\q'` can
+    /// still be stringized. Their values are checked by `Token::check` once
+    /// they become tokens.
+    pub fn pp_tokens(mut self, pp: bool) -> Self {
+        self.pp_tokens = pp;
+        self
+    }
+
+    /// Drains the lexer, reporting every lexical error rather than stopping
+    /// at the first one.
+    pub fn tokenize(self) -> Result<Vec<Token>, Vec<LexError>> {
+        let (tokens, errors): (Vec<_>, Vec<_>) = self.partition(Result::is_ok);
+
+        if errors.is_empty() {
+            Ok(tokens.into_iter().map(Result::unwrap).collect())
+        } else {
+            Err(errors.into_iter().map(Result::unwrap_err).collect())
+        }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.peek_nth(0)
+    }
+
+    fn peek_nth(&self, n: usize) -> Option<char> {
+        self.input.text()[self.offset..].chars().nth(n)
+    }
+
+    fn bump(&mut self) {
+        if let Some(c) = self.peek() {
+            self.offset += c.len_utf8();
+        }
+    }
+
+    fn bump_while(&mut self, p: impl Fn(char) -> bool) {
+        while self.peek().is_some_and(&p) {
+            self.bump();
+        }
+    }
+
+    // the physical span from start to the cursor
+    fn span(&self, start: usize) -> Span {
+        self.input.span(self.file, start, self.offset)
+    }
+
+    fn token(&self, start: usize, typ: TokenType) -> Token {
+        let span = self.span(start);
+        Token {
+            lexeme: Lexeme::Spelled(span),
+            typ,
+            span,
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        if self.keep_newlines {
+            self.bump_while(|c| c.is_whitespace() && c != '\n')
+        } else {
+            self.bump_while(char::is_whitespace)
+        }
+    }
+
+    // skips whitespace and comments, stopping early at a comment or newline
+    // if they are being kept
+    fn skip_trivia(&mut self) -> Option<Result<Token, LexError>> {
+        loop {
+            self.skip_whitespace();
+
+            let start = self.offset;
+            match (self.peek(), self.peek_nth(1)) {
+                (Some('/'), Some('*')) => {
+                    self.bump();
+                    self.bump();
+                    while !(self.peek() == Some('*') && self.peek_nth(1) == Some('/')) {
+                        if self.peek().is_none() {
+                            return Some(Err(LexError::UnterminatedComment {
+                                span: self.span(start),
+                            }));
+                        }
+                        self.bump();
+                    }
+                    self.bump();
+                    self.bump();
+                }
+                (Some('/'), Some('/')) if self.std >= Std::C99 => {
+                    self.bump_while(|c| c != '\n');
+                }
+                (Some('\n'), _) if self.keep_newlines => {
+                    self.bump();
+                    return Some(Ok(self.token(start, TokenType::Newline)));
+                }
+                _ => return None,
             }
-            _ => {
-                // panic
-                todo!()
+
+            if self.keep_comments {
+                return Some(Ok(self.token(start, TokenType::Comment)));
             }
-        },
-    }
-}
-
-// TODO: support identifiers with alpha*numeric* characters after first alphabetic
-fn scan_id(input: &[char]) -> Vec<Token> {
-    // scan_id calls skip_whitespace too to remain idempotent
-    let cs = skip_whitespace(input);
-
-    match cs {
-        [] => vec![],
-        [f, r @ ..] => match f {
-            'a'..='z' => {
-                // Find the index where the alphabetic characters end
-                #[rustfmt::skip]
-                let i = r
-                    .iter()
-                    .take_while(|&&c| c.is_alphabetic())
-                    .count();
-
-                let f = (cs[..=i].iter()).collect::<String>();
-                let new_r = &cs[i + 1..];
-
-                let keyword = match f.as_str() {
-                    "int" => Some(Token {
-                        lexeme: f.to_string(),
-                        typ: TokenType::KeywordInt,
-                    }),
-                    "main" => Some(Token {
-                        lexeme: f.to_string(),
-                        typ: TokenType::KeywordMain,
-                    }),
-                    "if" => Some(Token {
-                        lexeme: f.to_string(),
-                        typ: TokenType::KeywordIf,
-                    }),
-                    "else" => Some(Token {
-                        lexeme: f.to_string(),
-                        typ: TokenType::KeywordEls,
-                    }),
-                    "for" => Some(Token {
-                        lexeme: f.to_string(),
-                        typ: TokenType::KeywordFor,
-                    }),
-                    "return" => Some(Token {
-                        lexeme: f.to_string(),
-                        typ: TokenType::KeywordRet,
-                    }),
-                    _ => None,
-                };
-
-                let t = match keyword {
-                    Some(k) => k,
-                    None => Token {
-                        lexeme: f,
-                        typ: TokenType::Identifier,
-                    },
-                };
-
-                std::iter::once(t).chain(lex(new_r)).collect()
+        }
+    }
+
+    // scans a quoted literal up to its closing quote, which must be on the
+    // same line
+    fn scan_quoted(&mut self, quote: char, typ: TokenType) -> Result<Token, LexError> {
+        let start = self.offset;
+        self.bump();
+
+        loop {
+            match self.peek() {
+                Some(c) if c == quote => break,
+                None | Some('\n') => {
+                    return Err(LexError::UnterminatedLiteral {
+                        quote,
+                        span: self.span(start),
+                    })
+                }
+                Some('\\') if self.peek_nth(1).is_none() => {
+                    self.bump();
+                }
+                Some('\\') => {
+                    self.bump();
+                    self.bump();
+                }
+                Some(_) => self.bump(),
             }
-            _ => {
-                // panic
-                todo!()
+        }
+        self.bump();
+
+        let t = self.token(start, typ);
+        if !self.pp_tokens {
+            check(t, &self.input.text()[start..self.offset])?;
+        }
+        Ok(t)
+    }
+
+    // scans a preprocessing number, .?[0-9]([0-9a-zA-Z_.]|[eE][+-])*, so that
+    // malformed constants like 1foo, 0x or 1.2.3 are diagnosed as a whole
+    // rather than split into several tokens
+    fn scan_number(&mut self) -> Result<Token, LexError> {
+        let start = self.offset;
+        loop {
+            match (self.peek(), self.peek_nth(1)) {
+                (Some('e' | 'E'), Some('+' | '-')) => {
+                    self.bump();
+                    self.bump();
+                }
+                (Some(c), _) if c.is_ascii_alphanumeric() || c == '_' || c == '.' => self.bump(),
+                _ => break,
             }
-        },
+        }
+
+        let lexeme = &self.input.text()[start..self.offset];
+        let hex = lexeme.starts_with("0x") || lexeme.starts_with("0X");
+        let typ = if !hex && lexeme.contains(['.', 'e', 'E']) {
+            TokenType::LiteralFloat
+        } else {
+            TokenType::LiteralInt
+        };
+
+        let t = self.token(start, typ);
+        if !self.pp_tokens {
+            check(t, lexeme)?;
+        }
+        Ok(t)
+    }
+
+    fn scan_id(&mut self) -> Token {
+        let start = self.offset;
+        self.bump_while(|c| c.is_ascii_alphanumeric() || c == '_');
+
+        let name = &self.input.text()[start..self.offset];
+        Token {
+            lexeme: Lexeme::Symbol(Symbol::intern(name)),
+            typ: keyword(name).unwrap_or(TokenType::Identifier),
+            span: self.span(start),
+        }
     }
 }
 
-fn skip_whitespace(input: &[char]) -> &[char] {
-    match input {
-        [] => input,
-        [f, r @ ..] => {
-            if f.is_whitespace() {
-                skip_whitespace(r)
-            } else {
-                input
+impl Iterator for Lexer<'_> {
+    type Item = Result<Token, LexError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(trivia) = self.skip_trivia() {
+            return Some(trivia);
+        }
+
+        // literals and identifiers have arbitrary length
+        // operations and punctuations are at most three ASCII characters, and
+        // the longest one that matches wins (maximal munch): a+++b is a++ + b
+        let start = self.offset;
+        let c = self.peek()?;
+        let (typ, len) = match (c, self.peek_nth(1), self.peek_nth(2)) {
+            ('0'..='9', _, _) | ('.', Some('0'..='9'), _) => return Some(self.scan_number()),
+            ('\'', _, _) => return Some(self.scan_quoted('\'', TokenType::LiteralChar)),
+            ('"', _, _) => return Some(self.scan_quoted('"', TokenType::LiteralStr)),
+            ('a'..='z' | 'A'..='Z' | '_', _, _) => return Some(Ok(self.scan_id())),
+
+            ('<', Some('<'), Some('=')) => (TokenType::DoubleLeftAngleBracketEquals, 3),
+            ('>', Some('>'), Some('=')) => (TokenType::DoubleRightAngleBracketEquals, 3),
+            ('.', Some('.'), Some('.')) => (TokenType::PuncEllipsis, 3),
+
+            ('+', Some('+'), _) => (TokenType::PlusPlus, 2),
+            ('+', Some('='), _) => (TokenType::PlusEquals, 2),
+            ('-', Some('-'), _) => (TokenType::MinusMinus, 2),
+            ('-', Some('='), _) => (TokenType::MinusEquals, 2),
+            ('-', Some('>'), _) => (TokenType::Arrow, 2),
+            ('*', Some('='), _) => (TokenType::StarEquals, 2),
+            ('/', Some('='), _) => (TokenType::SlashEquals, 2),
+            ('%', Some('='), _) => (TokenType::PercentEquals, 2),
+            ('<', Some('<'), _) => (TokenType::DoubleLeftAngleBracket, 2),
+            ('<', Some('='), _) => (TokenType::LeftAngleBracketEquals, 2),
+            ('>', Some('>'), _) => (TokenType::DoubleRightAngleBracket, 2),
+            ('>', Some('='), _) => (TokenType::RightAngleBracketEquals, 2),
+            ('=', Some('='), _) => (TokenType::EqualsEquals, 2),
+            ('!', Some('='), _) => (TokenType::BangEquals, 2),
+            ('&', Some('&'), _) => (TokenType::AmpAmp, 2),
+            ('&', Some('='), _) => (TokenType::AmpEquals, 2),
+            ('|', Some('|'), _) => (TokenType::BarBar, 2),
+            ('|', Some('='), _) => (TokenType::BarEquals, 2),
+            ('^', Some('='), _) => (TokenType::CaretEquals, 2),
+            ('#', Some('#'), _) => (TokenType::HashHash, 2),
+
+            ('+', _, _) => (TokenType::Plus, 1),
+            ('-', _, _) => (TokenType::Minus, 1),
+            ('*', _, _) => (TokenType::Star, 1),
+            ('/', _, _) => (TokenType::Slash, 1),
+            ('%', _, _) => (TokenType::Percent, 1),
+            ('<', _, _) => (TokenType::LeftAngleBracket, 1),
+            ('>', _, _) => (TokenType::RightAngleBracket, 1),
+            ('=', _, _) => (TokenType::Equals, 1),
+            ('!', _, _) => (TokenType::Bang, 1),
+            ('&', _, _) => (TokenType::Amp, 1),
+            ('|', _, _) => (TokenType::Bar, 1),
+            ('^', _, _) => (TokenType::Caret, 1),
+            ('~', _, _) => (TokenType::Tilde, 1),
+            ('?', _, _) => (TokenType::Question, 1),
+            ('.', _, _) => (TokenType::Dot, 1),
+            ('(', _, _) => (TokenType::PuncLeftParen, 1),
+            (')', _, _) => (TokenType::PuncRightParen, 1),
+            ('{', _, _) => (TokenType::PuncLeftBrace, 1),
+            ('}', _, _) => (TokenType::PuncRightBrace, 1),
+            ('[', _, _) => (TokenType::PuncLeftBracket, 1),
+            (']', _, _) => (TokenType::PuncRightBracket, 1),
+            (';', _, _) => (TokenType::PuncSemiColon, 1),
+            (':', _, _) => (TokenType::PuncColon, 1),
+            (',', _, _) => (TokenType::PuncComma, 1),
+            ('#', _, _) => (TokenType::Hash, 1),
+            (c, _, _) => {
+                self.bump();
+                return Some(Err(LexError::IllegalChar {
+                    c,
+                    span: self.span(start),
+                }));
             }
+        };
+
+        for _ in 0..len {
+            self.bump();
         }
+        Some(Ok(self.token(start, typ)))
     }
 }
 
+// the 32 keywords of C89
+fn keyword(id: &str) -> Option<TokenType> {
+    let typ = match id {
+        "auto" => TokenType::KeywordAuto,
+        "break" => TokenType::KeywordBreak,
+        "case" => TokenType::KeywordCase,
+        "char" => TokenType::KeywordChar,
+        "const" => TokenType::KeywordConst,
+        "continue" => TokenType::KeywordContinue,
+        "default" => TokenType::KeywordDefault,
+        "do" => TokenType::KeywordDo,
+        "double" => TokenType::KeywordDouble,
+        "else" => TokenType::KeywordEls,
+        "enum" => TokenType::KeywordEnum,
+        "extern" => TokenType::KeywordExtern,
+        "float" => TokenType::KeywordFloat,
+        "for" => TokenType::KeywordFor,
+        "goto" => TokenType::KeywordGoto,
+        "if" => TokenType::KeywordIf,
+        "int" => TokenType::KeywordInt,
+        "long" => TokenType::KeywordLong,
+        "register" => TokenType::KeywordRegister,
+        "return" => TokenType::KeywordRet,
+        "short" => TokenType::KeywordShort,
+        "signed" => TokenType::KeywordSigned,
+        "sizeof" => TokenType::KeywordSizeof,
+        "static" => TokenType::KeywordStatic,
+        "struct" => TokenType::KeywordStruct,
+        "switch" => TokenType::KeywordSwitch,
+        "typedef" => TokenType::KeywordTypedef,
+        "union" => TokenType::KeywordUnion,
+        "unsigned" => TokenType::KeywordUnsigned,
+        "void" => TokenType::KeywordVoid,
+        "volatile" => TokenType::KeywordVolatile,
+        "while" => TokenType::KeywordWhile,
+        _ => return None,
+    };
+
+    Some(typ)
+}
+
+/// Scans the whole input as C89, dropping comments.
+pub fn lex(input: &str, file: FileId) -> Result<Vec<Token>, Vec<LexError>> {
+    Lexer::new(input, file).tokenize()
+}
+
+#[cfg(test)]
+#[derive(Serialize, Debug)]
+struct Spelled {
+    lexeme: String,
+    typ: TokenType,
+    span: Span,
+}
+
+// tokens as they read in the snapshots, with each lexeme spelled out
+#[cfg(test)]
+fn spelled(input: &str, tokens: &[Token]) -> Vec<Spelled> {
+    let mut source_map = SourceMap::new();
+    source_map.add("<test>", input);
+    tokens
+        .iter()
+        .map(|t| Spelled {
+            lexeme: t.text(&source_map).into_owned(),
+            typ: t.typ,
+            span: t.span,
+        })
+        .collect()
+}
+
 #[cfg(test)]
 mod test_legal_arithmetic {
+    use crate::source::FileId;
     use std::fs;
 
     const TEST_DIR: &str = "tests/fixtures/din/legal/arithmetic";
 
     #[test]
     fn lit() {
-        #[rustfmt::skip]
-        let input = fs::read(format!("{TEST_DIR}/lit.c"))
-            .expect("Should have been able to read the file")
-            .iter()
-            .map(|b| *b as char)
-            .collect::<Vec<_>>();
+        let input = fs::read_to_string(format!("{TEST_DIR}/lit.c"))
+            .expect("Should have been able to read the file");
 
-        let output = super::lex(input.as_slice());
-        insta::assert_yaml_snapshot!(output, @r###"
+        let output = super::lex(&input, FileId(0)).unwrap();
+        insta::assert_yaml_snapshot!(super::spelled(&input, &output), @r###"
         ---
         - lexeme: int
           typ: KeywordInt
+          span:
+            file: 0
+            lo: 0
+            hi: 3
         - lexeme: main
-          typ: KeywordMain
+          typ: Identifier
+          span:
+            file: 0
+            lo: 4
+            hi: 8
         - lexeme: (
           typ: PuncLeftParen
+          span:
+            file: 0
+            lo: 8
+            hi: 9
         - lexeme: )
           typ: PuncRightParen
+          span:
+            file: 0
+            lo: 9
+            hi: 10
         - lexeme: "{"
           typ: PuncLeftBrace
+          span:
+            file: 0
+            lo: 11
+            hi: 12
         - lexeme: return
           typ: KeywordRet
+          span:
+            file: 0
+            lo: 15
+            hi: 21
         - lexeme: "8"
           typ: LiteralInt
+          span:
+            file: 0
+            lo: 22
+            hi: 23
         - lexeme: ;
           typ: PuncSemiColon
+          span:
+            file: 0
+            lo: 23
+            hi: 24
         - lexeme: "}"
           typ: PuncRightBrace
+          span:
+            file: 0
+            lo: 25
+            hi: 26
         "###);
     }
 
     #[test]
     fn add() {
-        #[rustfmt::skip]
-        let input = fs::read(format!("{TEST_DIR}/add.c"))
-            .expect("Should have been able to read the file")
-            .iter()
-            .map(|b| *b as char)
-            .collect::<Vec<_>>();
+        let input = fs::read_to_string(format!("{TEST_DIR}/add.c"))
+            .expect("Should have been able to read the file");
 
-        let output = super::lex(input.as_slice());
-        insta::assert_yaml_snapshot!(output, @r###"
+        let output = super::lex(&input, FileId(0)).unwrap();
+        insta::assert_yaml_snapshot!(super::spelled(&input, &output), @r###"
         ---
         - lexeme: int
           typ: KeywordInt
+          span:
+            file: 0
+            lo: 0
+            hi: 3
         - lexeme: main
-          typ: KeywordMain
+          typ: Identifier
+          span:
+            file: 0
+            lo: 4
+            hi: 8
         - lexeme: (
           typ: PuncLeftParen
+          span:
+            file: 0
+            lo: 8
+            hi: 9
         - lexeme: )
           typ: PuncRightParen
+          span:
+            file: 0
+            lo: 9
+            hi: 10
         - lexeme: "{"
           typ: PuncLeftBrace
+          span:
+            file: 0
+            lo: 11
+            hi: 12
         - lexeme: return
           typ: KeywordRet
+          span:
+            file: 0
+            lo: 15
+            hi: 21
         - lexeme: "9"
           typ: LiteralInt
+          span:
+            file: 0
+            lo: 22
+            hi: 23
         - lexeme: +
           typ: Plus
+          span:
+            file: 0
+            lo: 24
+            hi: 25
         - lexeme: "10"
           typ: LiteralInt
+          span:
+            file: 0
+            lo: 26
+            hi: 28
         - lexeme: ;
           typ: PuncSemiColon
+          span:
+            file: 0
+            lo: 28
+            hi: 29
         - lexeme: "}"
           typ: PuncRightBrace
+          span:
+            file: 0
+            lo: 30
+            hi: 31
         "###);
     }
 
     #[test]
     fn add_multi() {
-        #[rustfmt::skip]
-        let input = fs::read(format!("{TEST_DIR}/add_multi.c"))
-            .expect("Should have been able to read the file")
-            .iter()
-            .map(|b| *b as char)
-            .collect::<Vec<_>>();
+        let input = fs::read_to_string(format!("{TEST_DIR}/add_multi.c"))
+            .expect("Should have been able to read the file");
 
-        let output = super::lex(input.as_slice());
-        insta::assert_yaml_snapshot!(output, @r###"
+        let output = super::lex(&input, FileId(0)).unwrap();
+        insta::assert_yaml_snapshot!(super::spelled(&input, &output), @r###"
         ---
         - lexeme: int
           typ: KeywordInt
+          span:
+            file: 0
+            lo: 0
+            hi: 3
         - lexeme: main
-          typ: KeywordMain
+          typ: Identifier
+          span:
+            file: 0
+            lo: 4
+            hi: 8
         - lexeme: (
           typ: PuncLeftParen
+          span:
+            file: 0
+            lo: 8
+            hi: 9
         - lexeme: )
           typ: PuncRightParen
+          span:
+            file: 0
+            lo: 9
+            hi: 10
         - lexeme: "{"
           typ: PuncLeftBrace
+          span:
+            file: 0
+            lo: 11
+            hi: 12
         - lexeme: return
           typ: KeywordRet
+          span:
+            file: 0
+            lo: 15
+            hi: 21
         - lexeme: "9"
           typ: LiteralInt
+          span:
+            file: 0
+            lo: 22
+            hi: 23
         - lexeme: +
           typ: Plus
+          span:
+            file: 0
+            lo: 24
+            hi: 25
         - lexeme: "10"
           typ: LiteralInt
+          span:
+            file: 0
+            lo: 26
+            hi: 28
         - lexeme: +
           typ: Plus
+          span:
+            file: 0
+            lo: 29
+            hi: 30
         - lexeme: "11"
           typ: LiteralInt
+          span:
+            file: 0
+            lo: 31
+            hi: 33
         - lexeme: ;
           typ: PuncSemiColon
+          span:
+            file: 0
+            lo: 33
+            hi: 34
         - lexeme: "}"
           typ: PuncRightBrace
+          span:
+            file: 0
+            lo: 35
+            hi: 36
         "###);
     }
 
     #[test]
     fn sub() {
-        #[rustfmt::skip]
-        let input = fs::read(format!("{}/sub.c", TEST_DIR))
-            .expect("Should have been able to read the file")
-            .iter()
-            .map(|b| *b as char)
-            .collect::<Vec<_>>();
+        let input = fs::read_to_string(format!("{}/sub.c", TEST_DIR))
+            .expect("Should have been able to read the file");
 
-        let output = super::lex(input.as_slice());
-        insta::assert_yaml_snapshot!(output, @r###"
+        let output = super::lex(&input, FileId(0)).unwrap();
+        insta::assert_yaml_snapshot!(super::spelled(&input, &output), @r###"
         ---
         - lexeme: int
           typ: KeywordInt
+          span:
+            file: 0
+            lo: 0
+            hi: 3
         - lexeme: main
-          typ: KeywordMain
+          typ: Identifier
+          span:
+            file: 0
+            lo: 4
+            hi: 8
         - lexeme: (
           typ: PuncLeftParen
+          span:
+            file: 0
+            lo: 8
+            hi: 9
         - lexeme: )
           typ: PuncRightParen
+          span:
+            file: 0
+            lo: 9
+            hi: 10
         - lexeme: "{"
           typ: PuncLeftBrace
+          span:
+            file: 0
+            lo: 11
+            hi: 12
         - lexeme: return
           typ: KeywordRet
+          span:
+            file: 0
+            lo: 15
+            hi: 21
         - lexeme: "88"
           typ: LiteralInt
+          span:
+            file: 0
+            lo: 22
+            hi: 24
         - lexeme: "-"
           typ: Minus
+          span:
+            file: 0
+            lo: 24
+            hi: 25
         - lexeme: "32"
           typ: LiteralInt
+          span:
+            file: 0
+            lo: 25
+            hi: 27
         - lexeme: ;
           typ: PuncSemiColon
+          span:
+            file: 0
+            lo: 27
+            hi: 28
         - lexeme: "}"
           typ: PuncRightBrace
+          span:
+            file: 0
+            lo: 29
+            hi: 30
         "###);
     }
 
     #[test]
     fn mult() {
-        #[rustfmt::skip]
-        let input = fs::read(format!("{TEST_DIR}/mult.c"))
-            .expect("Should have been able to read the file")
-            .iter()
-            .map(|b| *b as char)
-            .collect::<Vec<_>>();
+        let input = fs::read_to_string(format!("{TEST_DIR}/mult.c"))
+            .expect("Should have been able to read the file");
 
-        let output = super::lex(input.as_slice());
-        insta::assert_yaml_snapshot!(output, @r###"
+        let output = super::lex(&input, FileId(0)).unwrap();
+        insta::assert_yaml_snapshot!(super::spelled(&input, &output), @r###"
         ---
         - lexeme: int
           typ: KeywordInt
+          span:
+            file: 0
+            lo: 0
+            hi: 3
         - lexeme: main
-          typ: KeywordMain
+          typ: Identifier
+          span:
+            file: 0
+            lo: 4
+            hi: 8
         - lexeme: (
           typ: PuncLeftParen
+          span:
+            file: 0
+            lo: 8
+            hi: 9
         - lexeme: )
           typ: PuncRightParen
+          span:
+            file: 0
+            lo: 9
+            hi: 10
         - lexeme: "{"
           typ: PuncLeftBrace
+          span:
+            file: 0
+            lo: 11
+            hi: 12
         - lexeme: return
           typ: KeywordRet
+          span:
+            file: 0
+            lo: 15
+            hi: 21
         - lexeme: "9"
           typ: LiteralInt
+          span:
+            file: 0
+            lo: 22
+            hi: 23
         - lexeme: "*"
           typ: Star
+          span:
+            file: 0
+            lo: 24
+            hi: 25
         - lexeme: "10"
           typ: LiteralInt
+          span:
+            file: 0
+            lo: 26
+            hi: 28
         - lexeme: ;
           typ: PuncSemiColon
+          span:
+            file: 0
+            lo: 28
+            hi: 29
         - lexeme: "}"
           typ: PuncRightBrace
+          span:
+            file: 0
+            lo: 30
+            hi: 31
         "###);
     }
 
     #[test]
     fn div() {
-        #[rustfmt::skip]
-        let input = fs::read(format!("{TEST_DIR}/div.c"))
-            .expect("Should have been able to read the file")
-            .iter()
-            .map(|b| *b as char)
-            .collect::<Vec<_>>();
+        let input = fs::read_to_string(format!("{TEST_DIR}/div.c"))
+            .expect("Should have been able to read the file");
 
-        let output = super::lex(input.as_slice());
-        insta::assert_yaml_snapshot!(output, @r###"
+        let output = super::lex(&input, FileId(0)).unwrap();
+        insta::assert_yaml_snapshot!(super::spelled(&input, &output), @r###"
         ---
         - lexeme: int
           typ: KeywordInt
+          span:
+            file: 0
+            lo: 0
+            hi: 3
         - lexeme: main
-          typ: KeywordMain
+          typ: Identifier
+          span:
+            file: 0
+            lo: 4
+            hi: 8
         - lexeme: (
           typ: PuncLeftParen
+          span:
+            file: 0
+            lo: 8
+            hi: 9
         - lexeme: )
           typ: PuncRightParen
+          span:
+            file: 0
+            lo: 9
+            hi: 10
         - lexeme: "{"
           typ: PuncLeftBrace
+          span:
+            file: 0
+            lo: 11
+            hi: 12
         - lexeme: return
           typ: KeywordRet
+          span:
+            file: 0
+            lo: 15
+            hi: 21
         - lexeme: "100"
           typ: LiteralInt
+          span:
+            file: 0
+            lo: 22
+            hi: 25
         - lexeme: /
           typ: Slash
+          span:
+            file: 0
+            lo: 26
+            hi: 27
         - lexeme: "9"
           typ: LiteralInt
+          span:
+            file: 0
+            lo: 28
+            hi: 29
         - lexeme: ;
           typ: PuncSemiColon
+          span:
+            file: 0
+            lo: 29
+            hi: 30
         - lexeme: "}"
           typ: PuncRightBrace
+          span:
+            file: 0
+            lo: 31
+            hi: 32
         "###);
     }
 }
 
 #[cfg(test)]
 mod test_legal_control_flow {
+    use crate::source::FileId;
     use std::fs;
 
     const TEST_DIR: &str = "tests/fixtures/din/legal/control_flow";
 
     #[test]
     fn for_loop() {
-        #[rustfmt::skip]
-        let input = fs::read(format!("{TEST_DIR}/for.c"))
-            .expect("Should have been able to read the file")
-            .iter()
-            .map(|b| *b as char)
-            .collect::<Vec<_>>();
+        let input = fs::read_to_string(format!("{TEST_DIR}/for.c"))
+            .expect("Should have been able to read the file");
 
-        let output = super::lex(input.as_slice());
-        insta::assert_yaml_snapshot!(output, @r###"
+        let output = super::lex(&input, FileId(0)).unwrap();
+        insta::assert_yaml_snapshot!(super::spelled(&input, &output), @r###"
         ---
         - lexeme: int
           typ: KeywordInt
+          span:
+            file: 0
+            lo: 0
+            hi: 3
         - lexeme: main
-          typ: KeywordMain
+          typ: Identifier
+          span:
+            file: 0
+            lo: 4
+            hi: 8
         - lexeme: (
           typ: PuncLeftParen
+          span:
+            file: 0
+            lo: 8
+            hi: 9
         - lexeme: )
           typ: PuncRightParen
+          span:
+            file: 0
+            lo: 9
+            hi: 10
         - lexeme: "{"
           typ: PuncLeftBrace
+          span:
+            file: 0
+            lo: 11
+            hi: 12
         - lexeme: int
           typ: KeywordInt
+          span:
+            file: 0
+            lo: 17
+            hi: 20
         - lexeme: n
           typ: Identifier
+          span:
+            file: 0
+            lo: 21
+            hi: 22
         - lexeme: "="
           typ: Equals
+          span:
+            file: 0
+            lo: 23
+            hi: 24
         - lexeme: "0"
           typ: LiteralInt
+          span:
+            file: 0
+            lo: 25
+            hi: 26
         - lexeme: ;
           typ: PuncSemiColon
+          span:
+            file: 0
+            lo: 26
+            hi: 27
         - lexeme: for
           typ: KeywordFor
+          span:
+            file: 0
+            lo: 32
+            hi: 35
         - lexeme: (
           typ: PuncLeftParen
+          span:
+            file: 0
+            lo: 36
+            hi: 37
         - lexeme: int
           typ: KeywordInt
+          span:
+            file: 0
+            lo: 37
+            hi: 40
         - lexeme: i
           typ: Identifier
+          span:
+            file: 0
+            lo: 41
+            hi: 42
         - lexeme: "="
           typ: Equals
+          span:
+            file: 0
+            lo: 43
+            hi: 44
         - lexeme: "0"
           typ: LiteralInt
+          span:
+            file: 0
+            lo: 45
+            hi: 46
         - lexeme: ;
           typ: PuncSemiColon
+          span:
+            file: 0
+            lo: 46
+            hi: 47
         - lexeme: i
           typ: Identifier
+          span:
+            file: 0
+            lo: 48
+            hi: 49
         - lexeme: "<"
           typ: LeftAngleBracket
+          span:
+            file: 0
+            lo: 50
+            hi: 51
         - lexeme: "10"
           typ: LiteralInt
+          span:
+            file: 0
+            lo: 52
+            hi: 54
         - lexeme: ;
           typ: PuncSemiColon
+          span:
+            file: 0
+            lo: 54
+            hi: 55
         - lexeme: i
           typ: Identifier
-        - lexeme: +
-          typ: Plus
-        - lexeme: +
-          typ: Plus
+          span:
+            file: 0
+            lo: 56
+            hi: 57
+        - lexeme: ++
+          typ: PlusPlus
+          span:
+            file: 0
+            lo: 57
+            hi: 59
         - lexeme: )
           typ: PuncRightParen
+          span:
+            file: 0
+            lo: 59
+            hi: 60
         - lexeme: "{"
           typ: PuncLeftBrace
+          span:
+            file: 0
+            lo: 61
+            hi: 62
         - lexeme: n
           typ: Identifier
-        - lexeme: +
-          typ: Plus
-        - lexeme: "="
-          typ: Equals
+          span:
+            file: 0
+            lo: 71
+            hi: 72
+        - lexeme: +=
+          typ: PlusEquals
+          span:
+            file: 0
+            lo: 73
+            hi: 75
         - lexeme: "1"
           typ: LiteralInt
+          span:
+            file: 0
+            lo: 76
+            hi: 77
         - lexeme: ;
           typ: PuncSemiColon
+          span:
+            file: 0
+            lo: 77
+            hi: 78
         - lexeme: n
           typ: Identifier
-        - lexeme: +
-          typ: Plus
-        - lexeme: "="
-          typ: Equals
+          span:
+            file: 0
+            lo: 87
+            hi: 88
+        - lexeme: +=
+          typ: PlusEquals
+          span:
+            file: 0
+            lo: 89
+            hi: 91
         - lexeme: "1"
           typ: LiteralInt
+          span:
+            file: 0
+            lo: 92
+            hi: 93
         - lexeme: ;
           typ: PuncSemiColon
+          span:
+            file: 0
+            lo: 93
+            hi: 94
         - lexeme: "}"
           typ: PuncRightBrace
+          span:
+            file: 0
+            lo: 99
+            hi: 100
         - lexeme: return
           typ: KeywordRet
+          span:
+            file: 0
+            lo: 105
+            hi: 111
         - lexeme: n
           typ: Identifier
+          span:
+            file: 0
+            lo: 112
+            hi: 113
         - lexeme: ;
           typ: PuncSemiColon
+          span:
+            file: 0
+            lo: 113
+            hi: 114
         - lexeme: "}"
           typ: PuncRightBrace
+          span:
+            file: 0
+            lo: 115
+            hi: 116
         "###);
     }
 }
 
 #[cfg(test)]
 mod test_legal_data_flow {
+    use crate::source::FileId;
     use std::fs;
 
     const TEST_DIR: &str = "tests/fixtures/din/legal/data_flow";
 
     #[test]
     fn asnmt() {
-        #[rustfmt::skip]
-        let input = fs::read(format!("{TEST_DIR}/asnmt.c"))
-            .expect("Should have been able to read the file")
-            .iter()
-            .map(|b| *b as char)
-            .collect::<Vec<_>>();
+        let input = fs::read_to_string(format!("{TEST_DIR}/asnmt.c"))
+            .expect("Should have been able to read the file");
 
-        let output = super::lex(input.as_slice());
-        insta::assert_yaml_snapshot!(output, @r###"
+        let output = super::lex(&input, FileId(0)).unwrap();
+        insta::assert_yaml_snapshot!(super::spelled(&input, &output), @r###"
         ---
         - lexeme: int
           typ: KeywordInt
+          span:
+            file: 0
+            lo: 0
+            hi: 3
         - lexeme: main
-          typ: KeywordMain
+          typ: Identifier
+          span:
+            file: 0
+            lo: 4
+            hi: 8
         - lexeme: (
           typ: PuncLeftParen
+          span:
+            file: 0
+            lo: 8
+            hi: 9
         - lexeme: )
           typ: PuncRightParen
+          span:
+            file: 0
+            lo: 9
+            hi: 10
         - lexeme: "{"
           typ: PuncLeftBrace
+          span:
+            file: 0
+            lo: 11
+            hi: 12
         - lexeme: int
           typ: KeywordInt
+          span:
+            file: 0
+            lo: 17
+            hi: 20
         - lexeme: x
           typ: Identifier
+          span:
+            file: 0
+            lo: 21
+            hi: 22
         - lexeme: "="
           typ: Equals
+          span:
+            file: 0
+            lo: 23
+            hi: 24
         - lexeme: "8"
           typ: LiteralInt
+          span:
+            file: 0
+            lo: 25
+            hi: 26
         - lexeme: ;
           typ: PuncSemiColon
+          span:
+            file: 0
+            lo: 26
+            hi: 27
         - lexeme: return
           typ: KeywordRet
+          span:
+            file: 0
+            lo: 32
+            hi: 38
         - lexeme: x
           typ: Identifier
+          span:
+            file: 0
+            lo: 39
+            hi: 40
+        - lexeme: ;
+          typ: PuncSemiColon
+          span:
+            file: 0
+            lo: 40
+            hi: 41
+        - lexeme: "}"
+          typ: PuncRightBrace
+          span:
+            file: 0
+            lo: 42
+            hi: 43
+        "###);
+    }
+}
+
+#[cfg(test)]
+mod test_legal_comments {
+    use super::{Lexer, Std};
+    use crate::source::FileId;
+    use std::fs;
+
+    const TEST_DIR: &str = "tests/fixtures/din/legal/comments";
+
+    #[test]
+    fn block() {
+        let input = fs::read_to_string(format!("{TEST_DIR}/block.c"))
+            .expect("Should have been able to read the file");
+
+        let output = super::lex(&input, FileId(0)).unwrap();
+        insta::assert_yaml_snapshot!(super::spelled(&input, &output), @r###"
+        ---
+        - lexeme: int
+          typ: KeywordInt
+          span:
+            file: 0
+            lo: 40
+            hi: 43
+        - lexeme: main
+          typ: Identifier
+          span:
+            file: 0
+            lo: 44
+            hi: 48
+        - lexeme: (
+          typ: PuncLeftParen
+          span:
+            file: 0
+            lo: 48
+            hi: 49
+        - lexeme: )
+          typ: PuncRightParen
+          span:
+            file: 0
+            lo: 49
+            hi: 50
+        - lexeme: "{"
+          typ: PuncLeftBrace
+          span:
+            file: 0
+            lo: 51
+            hi: 52
+        - lexeme: int
+          typ: KeywordInt
+          span:
+            file: 0
+            lo: 57
+            hi: 60
+        - lexeme: x
+          typ: Identifier
+          span:
+            file: 0
+            lo: 61
+            hi: 62
+        - lexeme: "="
+          typ: Equals
+          span:
+            file: 0
+            lo: 63
+            hi: 64
+        - lexeme: "8"
+          typ: LiteralInt
+          span:
+            file: 0
+            lo: 65
+            hi: 66
+        - lexeme: ;
+          typ: PuncSemiColon
+          span:
+            file: 0
+            lo: 66
+            hi: 67
+        - lexeme: return
+          typ: KeywordRet
+          span:
+            file: 0
+            lo: 87
+            hi: 93
+        - lexeme: x
+          typ: Identifier
+          span:
+            file: 0
+            lo: 107
+            hi: 108
+        - lexeme: ;
+          typ: PuncSemiColon
+          span:
+            file: 0
+            lo: 108
+            hi: 109
+        - lexeme: "}"
+          typ: PuncRightBrace
+          span:
+            file: 0
+            lo: 110
+            hi: 111
+        "###);
+    }
+
+    #[test]
+    fn line_c89() {
+        let input = fs::read_to_string(format!("{TEST_DIR}/line.c"))
+            .expect("Should have been able to read the file");
+
+        let output = super::lex(&input, FileId(0)).unwrap();
+        let output = super::spelled(&input, &output);
+        assert_eq!(output[0].lexeme, "/");
+        assert_eq!(output[1].lexeme, "/");
+    }
+
+    #[test]
+    fn line_c99_trivia() {
+        let input = fs::read_to_string(format!("{TEST_DIR}/line.c"))
+            .expect("Should have been able to read the file");
+
+        let output = Lexer::new(&input, FileId(0))
+            .std(Std::C99)
+            .keep_comments(true)
+            .tokenize()
+            .unwrap();
+        insta::assert_yaml_snapshot!(super::spelled(&input, &output), @r###"
+        ---
+        - lexeme: // a line comment
+          typ: Comment
+          span:
+            file: 0
+            lo: 0
+            hi: 17
+        - lexeme: int
+          typ: KeywordInt
+          span:
+            file: 0
+            lo: 18
+            hi: 21
+        - lexeme: main
+          typ: Identifier
+          span:
+            file: 0
+            lo: 22
+            hi: 26
+        - lexeme: (
+          typ: PuncLeftParen
+          span:
+            file: 0
+            lo: 26
+            hi: 27
+        - lexeme: )
+          typ: PuncRightParen
+          span:
+            file: 0
+            lo: 27
+            hi: 28
+        - lexeme: "{"
+          typ: PuncLeftBrace
+          span:
+            file: 0
+            lo: 29
+            hi: 30
+        - lexeme: return
+          typ: KeywordRet
+          span:
+            file: 0
+            lo: 35
+            hi: 41
+        - lexeme: "9"
+          typ: LiteralInt
+          span:
+            file: 0
+            lo: 42
+            hi: 43
+        - lexeme: ;
+          typ: PuncSemiColon
+          span:
+            file: 0
+            lo: 43
+            hi: 44
+        - lexeme: // trailing /* not a block */
+          typ: Comment
+          span:
+            file: 0
+            lo: 45
+            hi: 74
+        - lexeme: "}"
+          typ: PuncRightBrace
+          span:
+            file: 0
+            lo: 75
+            hi: 76
+        "###);
+    }
+}
+
+#[cfg(test)]
+mod test_identifiers {
+    use crate::source::FileId;
+
+    #[test]
+    fn keywords() {
+        #[rustfmt::skip]
+        let input = "auto break case char const continue default do double else enum extern float for goto if int long register return short signed sizeof static struct switch typedef union unsigned void volatile while";
+
+        let output = super::lex(input, FileId(0))
+            .unwrap()
+            .into_iter()
+            .map(|t| t.typ)
+            .collect::<Vec<_>>();
+        insta::assert_yaml_snapshot!(output, @r###"
+        ---
+        - KeywordAuto
+        - KeywordBreak
+        - KeywordCase
+        - KeywordChar
+        - KeywordConst
+        - KeywordContinue
+        - KeywordDefault
+        - KeywordDo
+        - KeywordDouble
+        - KeywordEls
+        - KeywordEnum
+        - KeywordExtern
+        - KeywordFloat
+        - KeywordFor
+        - KeywordGoto
+        - KeywordIf
+        - KeywordInt
+        - KeywordLong
+        - KeywordRegister
+        - KeywordRet
+        - KeywordShort
+        - KeywordSigned
+        - KeywordSizeof
+        - KeywordStatic
+        - KeywordStruct
+        - KeywordSwitch
+        - KeywordTypedef
+        - KeywordUnion
+        - KeywordUnsigned
+        - KeywordVoid
+        - KeywordVolatile
+        - KeywordWhile
+        "###);
+    }
+
+    #[test]
+    fn identifiers() {
+        let input = "x1 my_var _tmp X __LINE__ ints iff";
+        let output = super::spelled(input, &super::lex(input, FileId(0)).unwrap())
+            .into_iter()
+            .map(|t| (t.lexeme, t.typ))
+            .collect::<Vec<_>>();
+        insta::assert_yaml_snapshot!(output, @r###"
+        ---
+        - - x1
+          - Identifier
+        - - my_var
+          - Identifier
+        - - _tmp
+          - Identifier
+        - - X
+          - Identifier
+        - - __LINE__
+          - Identifier
+        - - ints
+          - Identifier
+        - - iff
+          - Identifier
+        "###);
+    }
+}
+
+#[cfg(test)]
+mod test_legal_literals {
+    use crate::source::FileId;
+    use std::fs;
+
+    const TEST_DIR: &str = "tests/fixtures/din/legal/literals";
+
+    #[test]
+    fn char() {
+        let input = fs::read_to_string(format!("{TEST_DIR}/char.c"))
+            .expect("Should have been able to read the file");
+
+        let output = super::spelled(&input, &super::lex(&input, FileId(0)).unwrap())
+            .into_iter()
+            .map(|t| (t.lexeme, t.typ))
+            .collect::<Vec<_>>();
+        insta::assert_yaml_snapshot!(output, @r###"
+        ---
+        - - int
+          - KeywordInt
+        - - main
+          - Identifier
+        - - (
+          - PuncLeftParen
+        - - )
+          - PuncRightParen
+        - - "{"
+          - PuncLeftBrace
+        - - return
+          - KeywordRet
+        - - "'a'"
+          - LiteralChar
+        - - "-"
+          - Minus
+        - - "'\\n'"
+          - LiteralChar
+        - - +
+          - Plus
+        - - "'\\x41'"
+          - LiteralChar
+        - - "-"
+          - Minus
+        - - "'\\101'"
+          - LiteralChar
+        - - +
+          - Plus
+        - - "'\\\\'"
+          - LiteralChar
+        - - "-"
+          - Minus
+        - - "92"
+          - LiteralInt
+        - - ;
+          - PuncSemiColon
+        - - "}"
+          - PuncRightBrace
+        "###);
+    }
+
+    #[test]
+    fn str() {
+        let input = fs::read_to_string(format!("{TEST_DIR}/str.c"))
+            .expect("Should have been able to read the file");
+
+        let output = super::spelled(&input, &super::lex(&input, FileId(0)).unwrap())
+            .into_iter()
+            .map(|t| (t.lexeme, t.typ))
+            .collect::<Vec<_>>();
+        insta::assert_yaml_snapshot!(output, @r###"
+        ---
+        - - int
+          - KeywordInt
+        - - main
+          - Identifier
+        - - (
+          - PuncLeftParen
+        - - )
+          - PuncRightParen
+        - - "{"
+          - PuncLeftBrace
+        - - return
+          - KeywordRet
+        - - "\"hello, \""
+          - LiteralStr
+        - - "\"world\\n\\t\\\"\\0\""
+          - LiteralStr
+        - - ;
+          - PuncSemiColon
+        - - "}"
+          - PuncRightBrace
+        "###);
+    }
+
+    #[test]
+    fn float() {
+        let input = fs::read_to_string(format!("{TEST_DIR}/float.c"))
+            .expect("Should have been able to read the file");
+
+        let output = super::lex(&input, FileId(0)).unwrap();
+        let floats = super::spelled(&input, &output)
+            .iter()
+            .filter(|t| t.typ == super::TokenType::LiteralFloat)
+            .map(|t| super::float_literal(&t.lexeme, t.span).unwrap())
+            .collect::<Vec<_>>();
+        insta::assert_yaml_snapshot!(floats, @r###"
+        ---
+        - - 1.5
+          - Double
+        - - 0.0005
+          - Double
+        - - 2
+          - Float
+        - - 10000000000
+          - LongDouble
+        "###);
+    }
+
+    #[test]
+    fn utf8() {
+        let input = "/* naïve */ \"é\"";
+        let output = super::lex(input, FileId(0)).unwrap();
+        let output = super::spelled(input, &output);
+
+        let bytes = super::str_literal(&output[0].lexeme, output[0].span).unwrap();
+        assert_eq!(bytes, "é".as_bytes());
+    }
+
+    #[test]
+    fn pp_tokens() {
+        let input = "0x100000000 1.2.3 0x 08 '\\q'";
+        assert!(super::lex(input, FileId(0)).is_err());
+
+        let output = super::Lexer::new(input, FileId(0))
+            .pp_tokens(true)
+            .tokenize()
+            .unwrap();
+        let typs = output.iter().map(|t| t.typ).collect::<Vec<_>>();
+        insta::assert_yaml_snapshot!(typs, @r###"
+        ---
+        - LiteralInt
+        - LiteralFloat
+        - LiteralInt
+        - LiteralInt
+        - LiteralChar
+        "###);
+    }
+}
+
+#[cfg(test)]
+mod test_legal_splicing {
+    use crate::source::{FileId, SourceMap};
+    use std::fs;
+
+    const TEST_DIR: &str = "tests/fixtures/din/legal/splicing";
+
+    #[test]
+    fn trigraphs() {
+        let input = fs::read_to_string(format!("{TEST_DIR}/trigraphs.c"))
+            .expect("Should have been able to read the file");
+
+        let output = super::lex(&input, FileId(0)).unwrap();
+        let pairs = super::spelled(&input, &output)
+            .into_iter()
+            .map(|t| (t.lexeme, t.typ))
+            .collect::<Vec<_>>();
+        insta::assert_yaml_snapshot!(pairs, @r###"
+        ---
+        - - int
+          - KeywordInt
+        - - main
+          - Identifier
+        - - (
+          - PuncLeftParen
+        - - )
+          - PuncRightParen
+        - - "{"
+          - PuncLeftBrace
+        - - return
+          - KeywordRet
+        - - "42"
+          - LiteralInt
+        - - ;
+          - PuncSemiColon
+        - - "}"
+          - PuncRightBrace
+        "###);
+    }
+
+    #[test]
+    fn continuation() {
+        let input = fs::read_to_string(format!("{TEST_DIR}/continuation.c"))
+            .expect("Should have been able to read the file");
+
+        let output = super::lex(&input, FileId(0)).unwrap();
+        insta::assert_yaml_snapshot!(super::spelled(&input, &output), @r###"
+        ---
+        - lexeme: int
+          typ: KeywordInt
+          span:
+            file: 0
+            lo: 0
+            hi: 3
+        - lexeme: main
+          typ: Identifier
+          span:
+            file: 0
+            lo: 4
+            hi: 10
+        - lexeme: (
+          typ: PuncLeftParen
+          span:
+            file: 0
+            lo: 10
+            hi: 11
+        - lexeme: )
+          typ: PuncRightParen
+          span:
+            file: 0
+            lo: 11
+            hi: 12
+        - lexeme: "{"
+          typ: PuncLeftBrace
+          span:
+            file: 0
+            lo: 13
+            hi: 14
+        - lexeme: return
+          typ: KeywordRet
+          span:
+            file: 0
+            lo: 17
+            hi: 23
+        - lexeme: "42"
+          typ: LiteralInt
+          span:
+            file: 0
+            lo: 24
+            hi: 28
+        - lexeme: ;
+          typ: PuncSemiColon
+          span:
+            file: 0
+            lo: 28
+            hi: 29
+        - lexeme: "}"
+          typ: PuncRightBrace
+          span:
+            file: 0
+            lo: 30
+            hi: 31
+        "###);
+    }
+
+    #[test]
+    fn physical_location() {
+        let mut source_map = SourceMap::new();
+        let file = source_map.add("splice.c", "int x = 1 + \\\n  @;");
+
+        let errors = super::lex(source_map.file(file).src(), file).unwrap_err();
+        assert_eq!(
+            source_map.location(errors[0].span()).to_string(),
+            "splice.c:2:3"
+        );
+    }
+}
+
+#[cfg(test)]
+mod test_legal_whitespace {
+    use crate::source::FileId;
+    use std::fs;
+
+    const TEST_DIR: &str = "tests/fixtures/din/legal";
+
+    #[test]
+    fn newlines() {
+        let input = fs::read_to_string(format!("{TEST_DIR}/newlines.c"))
+            .expect("Should have been able to read the file");
+
+        let output = super::lex(&input, FileId(0)).unwrap();
+        insta::assert_yaml_snapshot!(super::spelled(&input, &output), @r###"
+        ---
+        - lexeme: int
+          typ: KeywordInt
+          span:
+            file: 0
+            lo: 0
+            hi: 3
+        - lexeme: main
+          typ: Identifier
+          span:
+            file: 0
+            lo: 5
+            hi: 9
+        - lexeme: (
+          typ: PuncLeftParen
+          span:
+            file: 0
+            lo: 10
+            hi: 11
+        - lexeme: )
+          typ: PuncRightParen
+          span:
+            file: 0
+            lo: 15
+            hi: 16
+        - lexeme: "{"
+          typ: PuncLeftBrace
+          span:
+            file: 0
+            lo: 17
+            hi: 18
+        - lexeme: return
+          typ: KeywordRet
+          span:
+            file: 0
+            lo: 19
+            hi: 25
+        - lexeme: "0"
+          typ: LiteralInt
+          span:
+            file: 0
+            lo: 26
+            hi: 27
         - lexeme: ;
           typ: PuncSemiColon
+          span:
+            file: 0
+            lo: 28
+            hi: 29
         - lexeme: "}"
           typ: PuncRightBrace
+          span:
+            file: 0
+            lo: 30
+            hi: 31
+        "###);
+    }
+
+    #[test]
+    fn logical_lines() {
+        let input = "#define X \\\n  1\nint /* a\nb */ x;";
+        let output = super::Lexer::new(input, FileId(0))
+            .keep_newlines(true)
+            .tokenize()
+            .unwrap();
+        let pairs = super::spelled(input, &output)
+            .into_iter()
+            .map(|t| (t.lexeme, t.typ))
+            .collect::<Vec<_>>();
+        insta::assert_yaml_snapshot!(pairs, @r###"
+        ---
+        - - "#"
+          - Hash
+        - - define
+          - Identifier
+        - - X
+          - Identifier
+        - - "1"
+          - LiteralInt
+        - - "\n"
+          - Newline
+        - - int
+          - KeywordInt
+        - - x
+          - Identifier
+        - - ;
+          - PuncSemiColon
+        "###);
+    }
+}
+
+#[cfg(test)]
+mod test_illegal {
+    use crate::source::{FileId, SourceMap};
+    use std::fs;
+
+    const TEST_DIR: &str = "tests/fixtures/din/illegal";
+
+    #[test]
+    fn at() {
+        let input = fs::read_to_string(format!("{TEST_DIR}/at.c"))
+            .expect("Should have been able to read the file");
+
+        let output = super::lex(&input, FileId(0)).unwrap_err();
+        insta::assert_yaml_snapshot!(output, @r###"
+        ---
+        - IllegalChar:
+            c: "@"
+            span:
+              file: 0
+              lo: 25
+              hi: 26
+        "###);
+    }
+
+    #[test]
+    fn backtick() {
+        let input = fs::read_to_string(format!("{TEST_DIR}/backtick.c"))
+            .expect("Should have been able to read the file");
+
+        let output = super::lex(&input, FileId(0)).unwrap_err();
+        insta::assert_yaml_snapshot!(output, @r###"
+        ---
+        - IllegalChar:
+            c: "`"
+            span:
+              file: 0
+              lo: 0
+              hi: 1
+        "###);
+    }
+
+    #[test]
+    fn backslash() {
+        let input = fs::read_to_string(format!("{TEST_DIR}/backslash.c"))
+            .expect("Should have been able to read the file");
+
+        let output = super::lex(&input, FileId(0)).unwrap_err();
+        insta::assert_yaml_snapshot!(output, @r###"
+        ---
+        - IllegalChar:
+            c: "\\"
+            span:
+              file: 0
+              lo: 0
+              hi: 1
+        "###);
+    }
+
+    #[test]
+    fn unterminated_comment() {
+        let input = fs::read_to_string(format!("{TEST_DIR}/unterminated_comment.c"))
+            .expect("Should have been able to read the file");
+
+        let output = super::lex(&input, FileId(0)).unwrap_err();
+        insta::assert_yaml_snapshot!(output, @r###"
+        ---
+        - UnterminatedComment:
+            span:
+              file: 0
+              lo: 27
+              hi: 37
         "###);
     }
+
+    #[test]
+    fn id() {
+        let input = fs::read_to_string(format!("{TEST_DIR}/id.c"))
+            .expect("Should have been able to read the file");
+
+        let output = super::lex(&input, FileId(0)).unwrap_err();
+        insta::assert_yaml_snapshot!(output, @r###"
+        ---
+        - InvalidIntSuffix:
+            suffix: foo
+            span:
+              file: 0
+              lo: 24
+              hi: 28
+        "###);
+    }
+
+    #[test]
+    fn int_too_large() {
+        let input = fs::read_to_string(format!("{TEST_DIR}/int_too_large.c"))
+            .expect("Should have been able to read the file");
+
+        let output = super::lex(&input, FileId(0)).unwrap_err();
+        insta::assert_yaml_snapshot!(output, @r###"
+        ---
+        - IntTooLarge:
+            span:
+              file: 0
+              lo: 22
+              hi: 32
+        "###);
+    }
+
+    #[test]
+    fn octal_digit() {
+        let input = fs::read_to_string(format!("{TEST_DIR}/octal_digit.c"))
+            .expect("Should have been able to read the file");
+
+        let output = super::lex(&input, FileId(0)).unwrap_err();
+        insta::assert_yaml_snapshot!(output, @r###"
+        ---
+        - InvalidOctalDigit:
+            c: "9"
+            span:
+              file: 0
+              lo: 22
+              hi: 24
+        "###);
+    }
+
+    #[test]
+    fn bad_escape() {
+        let input = fs::read_to_string(format!("{TEST_DIR}/bad_escape.c"))
+            .expect("Should have been able to read the file");
+
+        let output = super::lex(&input, FileId(0)).unwrap_err();
+        insta::assert_yaml_snapshot!(output, @r###"
+        ---
+        - InvalidEscape:
+            c: q
+            span:
+              file: 0
+              lo: 22
+              hi: 26
+        "###);
+    }
+
+    #[test]
+    fn escape_out_of_range() {
+        let input = fs::read_to_string(format!("{TEST_DIR}/escape_out_of_range.c"))
+            .expect("Should have been able to read the file");
+
+        let output = super::lex(&input, FileId(0)).unwrap_err();
+        insta::assert_yaml_snapshot!(output, @r###"
+        ---
+        - HexEscapeOutOfRange:
+            span:
+              file: 0
+              lo: 22
+              hi: 29
+        "###);
+    }
+
+    #[test]
+    fn octal_escape_out_of_range() {
+        let input = fs::read_to_string(format!("{TEST_DIR}/octal_escape_out_of_range.c"))
+            .expect("Should have been able to read the file");
+
+        let output = super::lex(&input, FileId(0)).unwrap_err();
+        insta::assert_yaml_snapshot!(output, @r###"
+        ---
+        - OctalEscapeOutOfRange:
+            span:
+              file: 0
+              lo: 22
+              hi: 28
+        "###);
+    }
+
+    #[test]
+    fn bad_exponent() {
+        let input = fs::read_to_string(format!("{TEST_DIR}/bad_exponent.c"))
+            .expect("Should have been able to read the file");
+
+        let output = super::lex(&input, FileId(0)).unwrap_err();
+        insta::assert_yaml_snapshot!(output, @r###"
+        ---
+        - MalformedExponent:
+            span:
+              file: 0
+              lo: 22
+              hi: 25
+        "###);
+    }
+
+    #[test]
+    fn float_suffix() {
+        let input = fs::read_to_string(format!("{TEST_DIR}/float_suffix.c"))
+            .expect("Should have been able to read the file");
+
+        let output = super::lex(&input, FileId(0)).unwrap_err();
+        insta::assert_yaml_snapshot!(output, @r###"
+        ---
+        - InvalidFloatSuffix:
+            suffix: x
+            span:
+              file: 0
+              lo: 22
+              hi: 26
+        "###);
+    }
+
+    #[test]
+    fn decimal_points() {
+        let output = super::lex("1.2.3", FileId(0)).unwrap_err();
+        insta::assert_yaml_snapshot!(output, @r###"
+        ---
+        - TooManyDecimalPoints:
+            span:
+              file: 0
+              lo: 0
+              hi: 5
+        "###);
+    }
+
+    #[test]
+    fn empty_char() {
+        let input = fs::read_to_string(format!("{TEST_DIR}/empty_char.c"))
+            .expect("Should have been able to read the file");
+
+        let output = super::lex(&input, FileId(0)).unwrap_err();
+        insta::assert_yaml_snapshot!(output, @r###"
+        ---
+        - EmptyChar:
+            span:
+              file: 0
+              lo: 22
+              hi: 24
+        "###);
+    }
+
+    #[test]
+    fn unterminated_str() {
+        let input = fs::read_to_string(format!("{TEST_DIR}/unterminated_str.c"))
+            .expect("Should have been able to read the file");
+
+        let output = super::lex(&input, FileId(0)).unwrap_err();
+        insta::assert_yaml_snapshot!(output, @r###"
+        ---
+        - UnterminatedLiteral:
+            quote: "\""
+            span:
+              file: 0
+              lo: 22
+              hi: 27
+        "###);
+    }
+
+    #[test]
+    fn display() {
+        let mut source_map = SourceMap::new();
+        let file = source_map.add("at.c", "int main() {\n  return 0@1;\n}");
+
+        let output = super::lex(source_map.file(file).src(), file).unwrap_err();
+        let e = &output[0];
+        assert_eq!(
+            format!("{}: error: {e}", source_map.location(e.span())),
+            "at.c:2:11: error: illegal character '@' in program"
+        );
+    }
+}
+
+#[cfg(test)]
+mod test_punctuators {
+    use crate::source::FileId;
+
+    #[test]
+    fn maximal_munch() {
+        #[rustfmt::skip]
+        let input = "a+++b <<= >>= ... -> ## # a=-1 x<<y x&&y|z .. %= ^ ~ ? : , [ ]";
+
+        let output = super::spelled(input, &super::lex(input, FileId(0)).unwrap())
+            .into_iter()
+            .map(|t| (t.lexeme, t.typ))
+            .collect::<Vec<_>>();
+        insta::assert_yaml_snapshot!(output, @r###"
+        ---
+        - - a
+          - Identifier
+        - - ++
+          - PlusPlus
+        - - +
+          - Plus
+        - - b
+          - Identifier
+        - - "<<="
+          - DoubleLeftAngleBracketEquals
+        - - ">>="
+          - DoubleRightAngleBracketEquals
+        - - "..."
+          - PuncEllipsis
+        - - "->"
+          - Arrow
+        - - "##"
+          - HashHash
+        - - "#"
+          - Hash
+        - - a
+          - Identifier
+        - - "="
+          - Equals
+        - - "-"
+          - Minus
+        - - "1"
+          - LiteralInt
+        - - x
+          - Identifier
+        - - "<<"
+          - DoubleLeftAngleBracket
+        - - y
+          - Identifier
+        - - x
+          - Identifier
+        - - "&&"
+          - AmpAmp
+        - - y
+          - Identifier
+        - - "|"
+          - Bar
+        - - z
+          - Identifier
+        - - "."
+          - Dot
+        - - "."
+          - Dot
+        - - "%="
+          - PercentEquals
+        - - ^
+          - Caret
+        - - "~"
+          - Tilde
+        - - "?"
+          - Question
+        - - ":"
+          - PuncColon
+        - - ","
+          - PuncComma
+        - - "["
+          - PuncLeftBracket
+        - - "]"
+          - PuncRightBracket
+        "###);
+    }
+}
+
+#[cfg(test)]
+mod test_streaming {
+    use super::{Lexer, TokenType};
+    use crate::source::FileId;
+
+    #[test]
+    fn lazy() {
+        let input = "return 0@1;";
+        let mut lexer = Lexer::new(input, FileId(0));
+
+        assert_eq!(lexer.next().unwrap().unwrap().typ, TokenType::KeywordRet);
+        assert_eq!(lexer.next().unwrap().unwrap().typ, TokenType::LiteralInt);
+        assert!(lexer.next().unwrap().is_err());
+        assert_eq!(lexer.next().unwrap().unwrap().typ, TokenType::LiteralInt);
+        assert_eq!(lexer.next().unwrap().unwrap().typ, TokenType::PuncSemiColon);
+        assert!(lexer.next().is_none());
+    }
+
+    #[test]
+    fn large_input() {
+        // one stack frame per token used to overflow well before this
+        let input = "x = x + 1;\n".repeat(200_000);
+        let tokens = super::lex(&input, FileId(0)).unwrap();
+
+        assert_eq!(tokens.len(), 1_200_000);
+        assert_eq!(tokens.last().unwrap().span.hi, input.len() - 1);
+    }
 }
 
 // #[cfg(test)]
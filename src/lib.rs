@@ -1,4 +1,9 @@
 pub mod generator;
 pub mod lexer;
 pub mod parser;
+pub mod preprocessor;
+pub mod source;
+pub mod splice;
+pub mod symbol;
+pub mod target;
 pub mod typer;
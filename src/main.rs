@@ -1,9 +1,52 @@
-use din::{generator, lexer, parser};
-use std::{env, fs, io::Write};
+use din::{
+    generator, lexer, parser,
+    preprocessor::{PreprocessError, PreprocessWarning, Preprocessor},
+    source::SourceMap,
+    target::Target,
+};
+use std::{env, fs, io::Write, process};
+
+// searched after any -isystem directories, unless -nostdinc is given
+const SYSTEM_INCLUDE_DIRS: [&str; 2] = ["/usr/local/include", "/usr/include"];
 
 fn main() {
-    println!(
-        "
+    let mut std = lexer::Std::C89;
+    let mut preprocess_only = false;
+    let mut include_dirs = Vec::new();
+    let mut system_dirs = Vec::new();
+    let mut stdinc = true;
+    let mut macros = Vec::new(); // -D and -U, in order
+    let mut target = Target::default();
+    let mut src = None;
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-std=c89" | "-std=c90" => std = lexer::Std::C89,
+            "-std=c99" => std = lexer::Std::C99,
+            "-I" => include_dirs.extend(args.next()),
+            "-isystem" => system_dirs.extend(args.next()),
+            "-nostdinc" => stdinc = false,
+            "-E" => preprocess_only = true,
+            "-D" | "-U" => macros.extend(args.next().map(|m| (arg.clone(), m))),
+            _ if arg.starts_with("-I") => include_dirs.push(arg[2..].to_owned()),
+            _ if arg.starts_with("-D") || arg.starts_with("-U") => {
+                macros.push((arg[..2].to_owned(), arg[2..].to_owned()))
+            }
+            _ if arg.starts_with("-march=") => {
+                target = Target::parse(&arg["-march=".len()..]).unwrap_or_else(|| {
+                    eprintln!("din: error: unsupported ISA: {arg}");
+                    process::exit(1);
+                })
+            }
+            _ => src = Some(arg),
+        }
+    }
+    let src = src.expect("error: no source file given");
+
+    // -E writes the preprocessed source to stdout, so nothing else may
+    if !preprocess_only {
+        println!(
+            "
     ⠀⠀⠀⠀⠀⣼⣧⠀⠀⠀⠀⠀
     ⠀⠀⠀⠀⣼⣿⣿⣧⠀⠀⠀⠀
     ⠀⠀⠀⠾⠿⠿⠿⠿⠷⠀⠀⠀
@@ -12,20 +55,65 @@ fn main() {
     ⠾⠟⠿⠿⠿⠧⠼⠿⠿⠿⠻⠷
     din: C89/90 -> RV32I
     "
-    );
+        );
+        println!("Compiling source: {src}");
+    }
 
-    let src = env::args().nth(1).expect("error: no source file given");
-    println!("Compiling source: {src}");
+    let mut source_map = SourceMap::new();
+    let file = source_map.load(&src).unwrap_or_else(|e| {
+        eprintln!("din: error: {src}: {e}");
+        process::exit(1);
+    });
 
-    let chars = fs::read(src)
-        .expect("Should have been able to read the file")
-        .iter()
-        .map(|b| *b as char)
-        .collect::<Vec<_>>();
+    if stdinc {
+        system_dirs.extend(SYSTEM_INCLUDE_DIRS.map(String::from));
+    }
+    let mut warnings = Vec::new();
+    let mut preprocessor = Preprocessor::new(&mut source_map)
+        .std(std)
+        .target(target)
+        .warnings(&mut warnings);
+    for dir in include_dirs {
+        preprocessor = preprocessor.include_dir(dir);
+    }
+    for dir in system_dirs {
+        preprocessor = preprocessor.system_dir(dir);
+    }
+    for (flag, m) in macros {
+        preprocessor = match flag.as_str() {
+            "-D" => preprocessor.define(&m),
+            _ => preprocessor.undef(&m),
+        };
+    }
+    // like gcc, honour SOURCE_DATE_EPOCH for reproducible __DATE__ and __TIME__
+    if let Some(secs) = env::var("SOURCE_DATE_EPOCH")
+        .ok()
+        .and_then(|s| s.parse().ok())
+    {
+        preprocessor = preprocessor.timestamp(secs);
+    }
 
-    let tokens = lexer::lex(&chars);
-    let tree = parser::parse(tokens).unwrap();
-    let assembly = generator::gen(tree);
+    if preprocess_only {
+        let text = preprocessor.preprocess_to_text(file);
+        warn(&source_map, &warnings);
+        match text {
+            Ok(text) => print!("{text}"),
+            Err(errors) => report(&source_map, errors),
+        }
+        return;
+    }
+
+    let tokens = preprocessor.preprocess(file);
+    warn(&source_map, &warnings);
+    let tokens = tokens.unwrap_or_else(|errors| report(&source_map, errors));
+    let tree = parser::parse(tokens, &source_map).unwrap_or_else(|e| {
+        eprintln!("{}: error: {e}", source_map.location(e.span()));
+        process::exit(1);
+    });
+    let assembly = generator::gen(tree, target).unwrap_or_else(|e| {
+        eprintln!("din: error: {e}");
+        process::exit(1);
+    });
 
     let trgt = "./tmp.s";
     println!("Generating target: {trgt}");
@@ -33,3 +121,16 @@ fn main() {
     f.write_all(assembly.join("\n").as_bytes())
         .expect("Unable to write data");
 }
+
+fn warn(source_map: &SourceMap, warnings: &[PreprocessWarning]) {
+    for w in warnings {
+        eprintln!("{}: warning: {w}", source_map.location(w.span()));
+    }
+}
+
+fn report(source_map: &SourceMap, errors: Vec<PreprocessError>) -> ! {
+    for e in errors {
+        eprintln!("{}: error: {e}", source_map.location(e.span()));
+    }
+    process::exit(1);
+}
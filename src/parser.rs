@@ -1,41 +1,129 @@
-use crate::lexer::{Token, TokenType};
+use crate::{
+    lexer::{self, FloatType, IntType, LexError, Token, TokenType},
+    source::{SourceMap, Span},
+    symbol::Symbol,
+};
 use serde::{Deserialize, Serialize};
-use std::io;
+use thiserror::Error;
+
+#[derive(Error, Clone, PartialEq, Serialize, Deserialize, Debug)]
+pub enum ParseError {
+    #[error(transparent)]
+    Lex(#[from] LexError),
+    #[error("expected {expected:?}, found '{found}'")]
+    Expected {
+        expected: TokenType,
+        found: String,
+        span: Span,
+    },
+    #[error("unexpected '{found}'")]
+    Unexpected { found: String, span: Span },
+    #[error("expected a type, found '{found}'")]
+    ExpectedType { found: String, span: Span },
+    #[error("invalid combination of type specifiers: {specifiers}")]
+    TypeSpecifiers { specifiers: String, span: Span },
+    #[error("unexpected end of input")]
+    UnexpectedEnd { span: Span },
+}
+
+impl ParseError {
+    pub fn span(&self) -> Span {
+        match self {
+            ParseError::Lex(e) => e.span(),
+            ParseError::Expected { span, .. }
+            | ParseError::Unexpected { span, .. }
+            | ParseError::ExpectedType { span, .. }
+            | ParseError::TypeSpecifiers { span, .. }
+            | ParseError::UnexpectedEnd { span } => *span,
+        }
+    }
+}
 
+// a translation unit
 #[derive(Clone, PartialEq, Serialize, Deserialize, Debug)]
 pub struct Program {
-    pub main_function: MainFunction,
+    pub functions: Vec<Function>,
 }
 
 #[derive(Clone, PartialEq, Serialize, Deserialize, Debug)]
-pub struct MainFunction {
+pub struct Function {
+    pub ret: Type,
+    pub name: Id,
+    pub params: Vec<Param>,
     pub stmts: Vec<Stmt>,
 }
 
 #[derive(Clone, PartialEq, Serialize, Deserialize, Debug)]
-pub struct Id(pub String);
+pub struct Param {
+    pub typ: Type,
+    pub id: Id,
+}
+
+#[derive(Clone, PartialEq, Serialize, Deserialize, Debug)]
+pub enum Type {
+    Void,
+    // plain char is signed on some targets and unsigned on others
+    Char,
+    SChar,
+    UChar,
+    Short,
+    UShort,
+    Int,
+    UInt,
+    Long,
+    ULong,
+    Float,
+    Double,
+    LongDouble,
+    Pointer(Box<Type>),
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Serialize, Deserialize, Debug)]
+pub struct Id(pub Symbol);
 
 #[derive(Clone, PartialEq, Serialize, Deserialize, Debug)]
 pub enum Stmt {
-    // Continue,
-    // Break,
-    While,
-    // Dowhile,
-    // Switch
-    If,
+    Continue,
+    Break,
+    While {
+        cond: Box<Expr>,
+        body: Box<Stmt>,
+    },
+    DoWhile {
+        body: Box<Stmt>,
+        cond: Box<Expr>,
+    },
+    // case and default label a statement in the body, and control falls
+    // through from it to the ones after
+    Switch {
+        cond: Box<Expr>,
+        body: Box<Stmt>,
+    },
+    Case {
+        value: Expr, // has to be an integer constant
+        stmt: Box<Stmt>,
+    },
+    Default(Box<Stmt>),
+    If {
+        cond: Box<Expr>,
+        then: Box<Stmt>,
+    },
     IfEls {
         cond: Box<Expr>,
         then: Box<Stmt>,
         els: Box<Stmt>,
     },
+    // any of the clauses can be left out, and no condition loops forever
     For {
-        asnmt: Box<Asnmt>,
-        cond: Box<Expr>,
-        update: Box<Asnmt>,
-        body: Vec<Stmt>,
+        asnmt: Option<Box<Asnmt>>,
+        cond: Option<Box<Expr>>,
+        update: Option<Box<Asnmt>>,
+        body: Box<Stmt>,
     },
     Return(Expr),
     Asnmt(Asnmt),
+    Expr(Expr), // evaluated for its effect, like a call
+    Block(Vec<Stmt>),
 }
 
 #[derive(Clone, PartialEq, Serialize, Deserialize, Debug)]
@@ -44,6 +132,7 @@ pub enum Asnmt {
     CreateBind { id: Id, expr: Box<Expr> },
 
     // update
+    Bind { id: Id, expr: Box<Expr> },
     UpdateBind { id: Id, op: BinOp, expr: Box<Expr> },
 }
 
@@ -51,6 +140,10 @@ pub enum Asnmt {
 pub enum Expr {
     // eliminations (operations)
     Var(Id), // eliminates assignment
+    Call {
+        id: Id, // eliminates function definition
+        args: Vec<Expr>,
+    },
     LogE {
         op: LogOp,
         l: Box<Expr>,
@@ -77,14 +170,16 @@ pub enum Expr {
     },
 
     // introductions (operands)
-    // Char
+    Char(u8),
     // - sign: Signed/Unsighed
     Int(i128),
-    // - sign: Signed/Unsighed
-    // - length: Short/Long
-    // Float
-    // Double
-    Str(String),
+    UInt(i128),
+    Long(i128),
+    ULong(i128),
+    Float(f32),
+    Double(f64),
+    LongDouble(f64),
+    Str(Vec<u8>),
 }
 
 #[derive(Clone, PartialEq, Serialize, Deserialize, Debug)]
@@ -127,204 +222,402 @@ pub enum UnaryOp {
     Sub,
 }
 
-pub fn parse(tokens: Vec<Token>) -> Result<Program, io::Error> {
-    let main_function = parse_function(tokens)?;
-    Ok(Program { main_function })
+// what a parse function returns: the node and the tokens after it
+type Parsed<'a, T> = Result<(T, &'a [Token]), ParseError>;
+
+pub fn parse(tokens: Vec<Token>, source_map: &SourceMap) -> Result<Program, ParseError> {
+    // none of the pragmas the preprocessor passes on change how din
+    // compiles yet
+    let tokens = tokens
+        .into_iter()
+        .filter(|t| t.typ != TokenType::Pragma)
+        .collect::<Vec<_>>();
+
+    let mut functions = vec![];
+    let mut r = &tokens[..];
+    while !r.is_empty() {
+        let (f, r1) = parse_function(r, source_map).map_err(|e| match (e, tokens.last()) {
+            (ParseError::UnexpectedEnd { .. }, Some(last)) => ParseError::UnexpectedEnd {
+                span: Span {
+                    lo: last.span.hi,
+                    ..last.span
+                },
+            },
+            (e, _) => e,
+        })?;
+        functions.push(f);
+        r = r1;
+    }
+
+    Ok(Program { functions })
 }
 
-fn parse_function(tokens: Vec<Token>) -> Result<MainFunction, io::Error> {
-    let (_, r) = mtch(&tokens, TokenType::KeywordInt)?;
-    let (_, r) = mtch(r, TokenType::KeywordMain)?;
-    let (_, r) = mtch(r, TokenType::PuncLeftParen)?;
-    let (_, r) = mtch(r, TokenType::PuncRightParen)?;
-    let (_, r) = mtch(r, TokenType::PuncLeftBrace)?;
+fn parse_function<'a>(tokens: &'a [Token], source_map: &SourceMap) -> Parsed<'a, Function> {
+    let (ret, r) = parse_type(tokens, source_map)?;
+    let (name, r) = mtch(r, TokenType::Identifier, source_map)?;
+    let (_, r) = mtch(r, TokenType::PuncLeftParen, source_map)?;
+    let (params, r) = parse_params(r, source_map)?;
+    let (_, r) = mtch(r, TokenType::PuncRightParen, source_map)?;
+    let (stmts, r) = parse_block(r, source_map)?;
+
+    Ok((
+        Function {
+            ret,
+            name: id(name),
+            params,
+            stmts,
+        },
+        r,
+    ))
+}
 
-    let mut stmts = vec![];
-    let mut r0 = r;
-    while let Ok((s, r1)) = parse_stmt(r0) {
-        stmts.push(s);
-        r0 = r1;
+fn parse_params<'a>(tokens: &'a [Token], source_map: &SourceMap) -> Parsed<'a, Vec<Param>> {
+    // in a definition, f() and f(void) both take no parameters
+    match tokens {
+        [f, ..] if f.typ == TokenType::PuncRightParen => return Ok((vec![], tokens)),
+        [f, s, ..] if f.typ == TokenType::KeywordVoid && s.typ == TokenType::PuncRightParen => {
+            return Ok((vec![], &tokens[1..]))
+        }
+        _ => (),
+    }
+
+    let mut params = vec![];
+    let mut r = tokens;
+    loop {
+        let (typ, r1) = parse_type(r, source_map)?;
+        let (name, r1) = mtch(r1, TokenType::Identifier, source_map)?;
+        params.push(Param { typ, id: id(name) });
+
+        match r1 {
+            [f, r2 @ ..] if f.typ == TokenType::PuncComma => r = r2,
+            _ => return Ok((params, r1)),
+        }
     }
-    let (_, r) = mtch(r0, TokenType::PuncRightBrace)?;
+}
+
+// type specifiers in any order, like `long unsigned int`, then any *s
+fn parse_type<'a>(tokens: &'a [Token], source_map: &SourceMap) -> Parsed<'a, Type> {
+    let mut specifiers = vec![];
+    let mut r = tokens;
+    while let [f, r1 @ ..] = r {
+        match f.typ {
+            TokenType::KeywordVoid
+            | TokenType::KeywordChar
+            | TokenType::KeywordShort
+            | TokenType::KeywordInt
+            | TokenType::KeywordLong
+            | TokenType::KeywordFloat
+            | TokenType::KeywordDouble
+            | TokenType::KeywordSigned
+            | TokenType::KeywordUnsigned => specifiers.push(symbol(f).as_str()),
+            _ => break,
+        }
+        r = r1;
+    }
+
+    let Some(first) = tokens.first() else {
+        return Err(end());
+    };
+    specifiers.sort_unstable();
+    let mut typ = match specifiers[..] {
+        ["void"] => Type::Void,
+        ["char"] => Type::Char,
+        ["char", "signed"] => Type::SChar,
+        ["char", "unsigned"] => Type::UChar,
+        ["short"] | ["int", "short"] | ["short", "signed"] | ["int", "short", "signed"] => {
+            Type::Short
+        }
+        ["short", "unsigned"] | ["int", "short", "unsigned"] => Type::UShort,
+        ["int"] | ["signed"] | ["int", "signed"] => Type::Int,
+        ["unsigned"] | ["int", "unsigned"] => Type::UInt,
+        ["long"] | ["int", "long"] | ["long", "signed"] | ["int", "long", "signed"] => Type::Long,
+        ["long", "unsigned"] | ["int", "long", "unsigned"] => Type::ULong,
+        ["float"] => Type::Float,
+        ["double"] => Type::Double,
+        ["double", "long"] => Type::LongDouble,
+        [] => {
+            return Err(ParseError::ExpectedType {
+                found: first.text(source_map).into_owned(),
+                span: first.span,
+            })
+        }
+        _ => {
+            return Err(ParseError::TypeSpecifiers {
+                specifiers: specifiers.join(" "),
+                span: first.span,
+            })
+        }
+    };
 
-    if !r.is_empty() {
-        // panic?
+    while let [f, r1 @ ..] = r {
+        if f.typ != TokenType::Star {
+            break;
+        }
+        typ = Type::Pointer(Box::new(typ));
+        r = r1;
     }
 
-    Ok(MainFunction { stmts })
+    Ok((typ, r))
 }
 
-fn parse_asmt(tokens: &[Token]) -> Result<(Asnmt, &[Token]), io::Error> {
+fn parse_asmt<'a>(tokens: &'a [Token], source_map: &SourceMap) -> Parsed<'a, Asnmt> {
     match tokens {
-        [] => todo!(),
+        [] => Err(end()),
         [f, r @ ..] => match f.typ {
             TokenType::KeywordInt => {
-                let (idt, r) = mtch(r, TokenType::Identifier)?;
-                let (_, r) = mtch(r, TokenType::Equals)?;
-                let (expr, r) = parse_rel_expr(r)?;
+                let (idt, r) = mtch(r, TokenType::Identifier, source_map)?;
+                let (_, r) = mtch(r, TokenType::Equals, source_map)?;
+                let (expr, r) = parse_rel_expr(r, source_map)?;
 
                 Ok((
                     Asnmt::CreateBind {
-                        id: Id(idt.lexeme.to_owned()),
+                        id: id(idt),
                         expr: Box::new(expr),
                     },
                     r,
                 ))
             }
             TokenType::Identifier => match r {
-                [] => todo!(),
-                [s, t, r @ ..] => match (s.typ, t.typ) {
-                    (TokenType::Plus, TokenType::Equals) => {
-                        let (expr, r) = parse_rel_expr(r)?;
-                        Ok((
-                            Asnmt::UpdateBind {
-                                id: Id(f.lexeme.parse().unwrap()),
-                                op: BinOp::Add,
-                                expr: Box::new(expr),
-                            },
-                            r,
-                        ))
-                    }
-                    (TokenType::Plus, TokenType::Plus) => Ok((
-                        Asnmt::UpdateBind {
-                            id: Id(f.lexeme.parse().unwrap()),
-                            op: BinOp::Add,
-                            expr: Box::new(Expr::Int(1)),
-                        },
-                        r,
-                    )),
-                    (TokenType::Minus, TokenType::Equals) => {
-                        let (expr, r) = parse_rel_expr(r)?;
-
-                        Ok((
-                            Asnmt::UpdateBind {
-                                id: Id(f.lexeme.parse().unwrap()),
-                                op: BinOp::Sub,
-                                expr: Box::new(expr),
-                            },
-                            r,
-                        ))
-                    }
-                    (TokenType::Minus, TokenType::Minus) => Ok((
+                [] => Err(end()),
+                [s, r @ ..] => {
+                    let op = match s.typ {
+                        TokenType::Equals => {
+                            let (expr, r) = parse_rel_expr(r, source_map)?;
+                            return Ok((
+                                Asnmt::Bind {
+                                    id: id(f),
+                                    expr: Box::new(expr),
+                                },
+                                r,
+                            ));
+                        }
+                        TokenType::PlusPlus => {
+                            return Ok((
+                                Asnmt::UpdateBind {
+                                    id: id(f),
+                                    op: BinOp::Add,
+                                    expr: Box::new(Expr::Int(1)),
+                                },
+                                r,
+                            ))
+                        }
+                        TokenType::MinusMinus => {
+                            return Ok((
+                                Asnmt::UpdateBind {
+                                    id: id(f),
+                                    op: BinOp::Sub,
+                                    expr: Box::new(Expr::Int(1)),
+                                },
+                                r,
+                            ))
+                        }
+                        TokenType::PlusEquals => BinOp::Add,
+                        TokenType::MinusEquals => BinOp::Sub,
+                        TokenType::StarEquals => BinOp::Mult,
+                        TokenType::SlashEquals => BinOp::Div,
+                        TokenType::PercentEquals => BinOp::Mod,
+                        _ => return Err(unexpected(s, source_map)),
+                    };
+
+                    let (expr, r) = parse_rel_expr(r, source_map)?;
+                    Ok((
                         Asnmt::UpdateBind {
-                            id: Id(f.lexeme.parse().unwrap()),
-                            op: BinOp::Sub,
-                            expr: Box::new(Expr::Int(1)),
+                            id: id(f),
+                            op,
+                            expr: Box::new(expr),
                         },
                         r,
-                    )),
-                    (TokenType::Star, TokenType::Equals) => {
-                        let (expr, r) = parse_rel_expr(r)?;
-
-                        Ok((
-                            Asnmt::UpdateBind {
-                                id: Id(f.lexeme.parse().unwrap()),
-                                op: BinOp::Mult,
-                                expr: Box::new(expr),
-                            },
-                            r,
-                        ))
-                    }
-                    (TokenType::Slash, TokenType::Equals) => {
-                        let (expr, r) = parse_rel_expr(r)?;
-
-                        Ok((
-                            Asnmt::UpdateBind {
-                                id: Id(f.lexeme.parse().unwrap()),
-                                op: BinOp::Div,
-                                expr: Box::new(expr),
-                            },
-                            r,
-                        ))
-                    }
-                    t => {
-                        todo!()
-                    }
-                },
-                t => todo!(),
+                    ))
+                }
             },
-            t => Err(io::Error::new(
-                io::ErrorKind::Other,
-                format!("token not recognizable {:?}", t),
-            )),
+            _ => Err(unexpected(f, source_map)),
         },
     }
 }
 
-fn parse_stmt(tokens: &[Token]) -> Result<(Stmt, &[Token]), io::Error> {
+fn parse_stmt<'a>(tokens: &'a [Token], source_map: &SourceMap) -> Parsed<'a, Stmt> {
     match tokens {
-        [] => todo!(),
+        [] => Err(end()),
         [f, r @ ..] => match f.typ {
+            TokenType::Identifier
+                if r.first().is_some_and(|t| t.typ == TokenType::PuncLeftParen) =>
+            {
+                let (expr, r) = parse_rel_expr(tokens, source_map)?;
+                let (_, r) = mtch(r, TokenType::PuncSemiColon, source_map)?;
+                Ok((Stmt::Expr(expr), r))
+            }
             TokenType::KeywordInt | TokenType::Identifier => {
-                let (a, r) = parse_asmt(tokens)?;
-                let (_, r) = mtch(r, TokenType::PuncSemiColon)?;
+                let (a, r) = parse_asmt(tokens, source_map)?;
+                let (_, r) = mtch(r, TokenType::PuncSemiColon, source_map)?;
 
                 Ok((Stmt::Asnmt(a), r))
             }
+            TokenType::PuncLeftBrace => {
+                let (stmts, r) = parse_block(tokens, source_map)?;
+                Ok((Stmt::Block(stmts), r))
+            }
+            TokenType::KeywordSwitch => {
+                let (_, r) = mtch(r, TokenType::PuncLeftParen, source_map)?;
+                let (cond, r) = parse_rel_expr(r, source_map)?;
+                let (_, r) = mtch(r, TokenType::PuncRightParen, source_map)?;
+                let (body, r) = parse_stmt(r, source_map)?;
+
+                Ok((
+                    Stmt::Switch {
+                        cond: Box::new(cond),
+                        body: Box::new(body),
+                    },
+                    r,
+                ))
+            }
+            TokenType::KeywordCase => {
+                let (value, r) = parse_rel_expr(r, source_map)?;
+                let (_, r) = mtch(r, TokenType::PuncColon, source_map)?;
+                let (stmt, r) = parse_stmt(r, source_map)?;
+                Ok((
+                    Stmt::Case {
+                        value,
+                        stmt: Box::new(stmt),
+                    },
+                    r,
+                ))
+            }
+            TokenType::KeywordDefault => {
+                let (_, r) = mtch(r, TokenType::PuncColon, source_map)?;
+                let (stmt, r) = parse_stmt(r, source_map)?;
+                Ok((Stmt::Default(Box::new(stmt)), r))
+            }
+            TokenType::KeywordBreak => {
+                let (_, r) = mtch(r, TokenType::PuncSemiColon, source_map)?;
+                Ok((Stmt::Break, r))
+            }
+            TokenType::KeywordContinue => {
+                let (_, r) = mtch(r, TokenType::PuncSemiColon, source_map)?;
+                Ok((Stmt::Continue, r))
+            }
             TokenType::KeywordRet => {
-                let (expr, r) = parse_rel_expr(r)?;
-                let (_, r) = mtch(r, TokenType::PuncSemiColon)?;
+                let (expr, r) = parse_rel_expr(r, source_map)?;
+                let (_, r) = mtch(r, TokenType::PuncSemiColon, source_map)?;
                 Ok((Stmt::Return(expr), r))
             }
             TokenType::KeywordIf => {
-                let (_, r) = mtch(r, TokenType::PuncLeftParen)?;
-                let (cond, r) = parse_rel_expr(r)?;
-                let (_, r) = mtch(r, TokenType::PuncRightParen)?;
-                let (_, r) = mtch(r, TokenType::PuncLeftBrace)?;
-                let (then, r) = parse_stmt(r)?;
-                let (_, r) = mtch(r, TokenType::PuncRightBrace)?;
-                let (_, r) = mtch(r, TokenType::KeywordEls)?;
-                let (_, r) = mtch(r, TokenType::PuncLeftBrace)?;
-                let (els, r) = parse_stmt(r)?;
-                let (_, r) = mtch(r, TokenType::PuncRightBrace)?;
+                let (_, r) = mtch(r, TokenType::PuncLeftParen, source_map)?;
+                let (cond, r) = parse_rel_expr(r, source_map)?;
+                let (_, r) = mtch(r, TokenType::PuncRightParen, source_map)?;
+                let (then, r) = parse_stmt(r, source_map)?;
+
+                // an if nested in then has already taken any else after it,
+                // so an else belongs to the nearest if
+                match r {
+                    [e, r @ ..] if e.typ == TokenType::KeywordEls => {
+                        let (els, r) = parse_stmt(r, source_map)?;
+                        Ok((
+                            Stmt::IfEls {
+                                cond: Box::new(cond),
+                                then: Box::new(then),
+                                els: Box::new(els),
+                            },
+                            r,
+                        ))
+                    }
+                    _ => Ok((
+                        Stmt::If {
+                            cond: Box::new(cond),
+                            then: Box::new(then),
+                        },
+                        r,
+                    )),
+                }
+            }
+            TokenType::KeywordFor => {
+                let (_, r) = mtch(r, TokenType::PuncLeftParen, source_map)?;
+                let (asnmt, r) = optional(r, TokenType::PuncSemiColon, parse_asmt, source_map)?;
+                let (_, r) = mtch(r, TokenType::PuncSemiColon, source_map)?;
+                let (cond, r) = optional(r, TokenType::PuncSemiColon, parse_rel_expr, source_map)?;
+                let (_, r) = mtch(r, TokenType::PuncSemiColon, source_map)?;
+                let (update, r) = optional(r, TokenType::PuncRightParen, parse_asmt, source_map)?;
+                let (_, r) = mtch(r, TokenType::PuncRightParen, source_map)?;
+                let (body, r) = parse_stmt(r, source_map)?;
+
+                Ok((
+                    Stmt::For {
+                        asnmt: asnmt.map(Box::new),
+                        cond: cond.map(Box::new),
+                        update: update.map(Box::new),
+                        body: Box::new(body),
+                    },
+                    r,
+                ))
+            }
+            TokenType::KeywordWhile => {
+                let (_, r) = mtch(r, TokenType::PuncLeftParen, source_map)?;
+                let (cond, r) = parse_rel_expr(r, source_map)?;
+                let (_, r) = mtch(r, TokenType::PuncRightParen, source_map)?;
+                let (body, r) = parse_stmt(r, source_map)?;
 
                 Ok((
-                    Stmt::IfEls {
+                    Stmt::While {
                         cond: Box::new(cond),
-                        then: Box::new(then),
-                        els: Box::new(els),
+                        body: Box::new(body),
                     },
                     r,
                 ))
             }
-            TokenType::KeywordFor => {
-                let (_, r) = mtch(r, TokenType::PuncLeftParen)?;
-                let (asnmt, r) = parse_asmt(r)?;
-                let (_, r) = mtch(r, TokenType::PuncSemiColon)?;
-                let (cond, r) = parse_rel_expr(r)?;
-                let (_, r) = mtch(r, TokenType::PuncSemiColon)?;
-                let (update, r) = parse_asmt(r)?;
-                let (_, r) = mtch(r, TokenType::PuncRightParen)?;
-                let (_, r) = mtch(r, TokenType::PuncLeftBrace)?;
-
-                let mut body = vec![];
-                let mut r0 = r;
-                while let Ok((s, r1)) = parse_stmt(r0) {
-                    body.push(s);
-                    r0 = r1;
-                }
-                let (_, r) = mtch(r0, TokenType::PuncRightBrace)?;
+            TokenType::KeywordDo => {
+                let (body, r) = parse_stmt(r, source_map)?;
+                let (_, r) = mtch(r, TokenType::KeywordWhile, source_map)?;
+                let (_, r) = mtch(r, TokenType::PuncLeftParen, source_map)?;
+                let (cond, r) = parse_rel_expr(r, source_map)?;
+                let (_, r) = mtch(r, TokenType::PuncRightParen, source_map)?;
+                let (_, r) = mtch(r, TokenType::PuncSemiColon, source_map)?;
 
                 Ok((
-                    Stmt::For {
-                        asnmt: Box::new(asnmt),
+                    Stmt::DoWhile {
+                        body: Box::new(body),
                         cond: Box::new(cond),
-                        update: Box::new(update),
-                        body,
                     },
                     r,
                 ))
             }
-            t => Err(io::Error::new(
-                io::ErrorKind::Other,
-                format!("token not recognizable {:?}", t),
-            )),
+            _ => Err(unexpected(f, source_map)),
         },
     }
 }
 
-fn parse_rel_expr(tokens: &[Token]) -> Result<(Expr, &[Token]), io::Error> {
-    let (left, r) = parse_term(tokens)?;
+// a clause that is absent when the token that ends it comes first
+fn optional<'a, T>(
+    tokens: &'a [Token],
+    end: TokenType,
+    parse: fn(&'a [Token], &SourceMap) -> Parsed<'a, T>,
+    source_map: &SourceMap,
+) -> Parsed<'a, Option<T>> {
+    match tokens.first() {
+        Some(t) if t.typ == end => Ok((None, tokens)),
+        _ => parse(tokens, source_map).map(|(t, r)| (Some(t), r)),
+    }
+}
+
+// the statements between { and }
+fn parse_block<'a>(tokens: &'a [Token], source_map: &SourceMap) -> Parsed<'a, Vec<Stmt>> {
+    let (_, r) = mtch(tokens, TokenType::PuncLeftBrace, source_map)?;
+
+    let mut stmts = vec![];
+    let mut r0 = r;
+    while r0
+        .first()
+        .is_some_and(|t| t.typ != TokenType::PuncRightBrace)
+    {
+        let (s, r1) = parse_stmt(r0, source_map)?;
+        stmts.push(s);
+        r0 = r1;
+    }
+    let (_, r) = mtch(r0, TokenType::PuncRightBrace, source_map)?;
+
+    Ok((stmts, r))
+}
+
+fn parse_rel_expr<'a>(tokens: &'a [Token], source_map: &SourceMap) -> Parsed<'a, Expr> {
+    let (left, r) = parse_term(tokens, source_map)?;
 
     match r {
         [] => Ok((left, r)),
@@ -332,8 +625,8 @@ fn parse_rel_expr(tokens: &[Token]) -> Result<(Expr, &[Token]), io::Error> {
             let mut cur_node = left;
             let mut r = r;
 
-            while let Ok((op, r_temp)) = parse_rel_op(r) {
-                let (right, r_temp) = parse_term(r_temp)?;
+            while let Ok((op, r_temp)) = parse_rel_op(r, source_map) {
+                let (right, r_temp) = parse_term(r_temp, source_map)?;
 
                 cur_node = Expr::RelE {
                     op,
@@ -349,8 +642,8 @@ fn parse_rel_expr(tokens: &[Token]) -> Result<(Expr, &[Token]), io::Error> {
     }
 }
 
-fn parse_term(tokens: &[Token]) -> Result<(Expr, &[Token]), io::Error> {
-    let (left, r) = parse_factor(tokens)?;
+fn parse_term<'a>(tokens: &'a [Token], source_map: &SourceMap) -> Parsed<'a, Expr> {
+    let (left, r) = parse_factor(tokens, source_map)?;
 
     match r {
         [] => Ok((left, r)),
@@ -358,8 +651,8 @@ fn parse_term(tokens: &[Token]) -> Result<(Expr, &[Token]), io::Error> {
             let mut cur_node = left;
             let mut r = r;
 
-            while let Ok((op, r_temp)) = parse_term_op(r) {
-                let (right, r_temp) = parse_factor(r_temp)?;
+            while let Ok((op, r_temp)) = parse_term_op(r, source_map) {
+                let (right, r_temp) = parse_factor(r_temp, source_map)?;
 
                 cur_node = Expr::BinE {
                     op,
@@ -375,8 +668,8 @@ fn parse_term(tokens: &[Token]) -> Result<(Expr, &[Token]), io::Error> {
     }
 }
 
-fn parse_factor(tokens: &[Token]) -> Result<(Expr, &[Token]), io::Error> {
-    let (left, r) = parse_atom(tokens)?;
+fn parse_factor<'a>(tokens: &'a [Token], source_map: &SourceMap) -> Parsed<'a, Expr> {
+    let (left, r) = parse_atom(tokens, source_map)?;
 
     match r {
         [] => Ok((left, r)),
@@ -384,8 +677,8 @@ fn parse_factor(tokens: &[Token]) -> Result<(Expr, &[Token]), io::Error> {
             let mut cur_node = left;
             let mut r = r;
 
-            while let Ok((op, r_temp)) = parse_factor_op(r) {
-                let (right, r_temp) = parse_atom(r_temp)?;
+            while let Ok((op, r_temp)) = parse_factor_op(r, source_map) {
+                let (right, r_temp) = parse_atom(r_temp, source_map)?;
 
                 cur_node = Expr::BinE {
                     op,
@@ -401,712 +694,1290 @@ fn parse_factor(tokens: &[Token]) -> Result<(Expr, &[Token]), io::Error> {
     }
 }
 
-fn parse_atom(tokens: &[Token]) -> Result<(Expr, &[Token]), io::Error> {
+fn parse_atom<'a>(tokens: &'a [Token], source_map: &SourceMap) -> Parsed<'a, Expr> {
     match tokens {
-        [] => todo!(),
+        [] => Err(end()),
         [f, r @ ..] => match f.typ {
-            TokenType::Identifier => Ok((Expr::Var(Id(f.lexeme.to_owned())), r)),
-            TokenType::LiteralInt => Ok((Expr::Int(f.lexeme.parse().unwrap()), r)),
-            t => Err(io::Error::new(
-                io::ErrorKind::Other,
-                format!("token not recognizable {:?}", t),
-            )),
+            TokenType::Identifier => match r {
+                [s, r @ ..] if s.typ == TokenType::PuncLeftParen => {
+                    let (args, r) = parse_args(r, source_map)?;
+                    let (_, r) = mtch(r, TokenType::PuncRightParen, source_map)?;
+                    Ok((Expr::Call { id: id(f), args }, r))
+                }
+                _ => Ok((Expr::Var(id(f)), r)),
+            },
+            TokenType::LiteralInt => {
+                let (n, typ) = lexer::int_literal(&f.text(source_map), f.span)?;
+                let n = n as i128;
+                let lit = match typ {
+                    IntType::Int => Expr::Int(n),
+                    IntType::UnsignedInt => Expr::UInt(n),
+                    IntType::Long => Expr::Long(n),
+                    IntType::UnsignedLong => Expr::ULong(n),
+                };
+
+                Ok((lit, r))
+            }
+            TokenType::LiteralFloat => {
+                let (x, typ) = lexer::float_literal(&f.text(source_map), f.span)?;
+                let lit = match typ {
+                    FloatType::Float => Expr::Float(x as f32),
+                    FloatType::Double => Expr::Double(x),
+                    FloatType::LongDouble => Expr::LongDouble(x),
+                };
+
+                Ok((lit, r))
+            }
+            TokenType::LiteralChar => {
+                let c = lexer::char_literal(&f.text(source_map), f.span)?;
+                Ok((Expr::Char(c), r))
+            }
+            TokenType::LiteralStr => {
+                // adjacent string literals are concatenated: "a" "b" is "ab"
+                let mut bytes = vec![];
+                let mut r = tokens;
+                while let [f, r1 @ ..] = r {
+                    if f.typ != TokenType::LiteralStr {
+                        break;
+                    }
+                    bytes.extend(lexer::str_literal(&f.text(source_map), f.span)?);
+                    r = r1;
+                }
+
+                Ok((Expr::Str(bytes), r))
+            }
+            TokenType::Plus | TokenType::Minus => {
+                // a unary operator binds tighter than any binary one
+                let op = match f.typ {
+                    TokenType::Plus => UnaryOp::Add,
+                    _ => UnaryOp::Sub,
+                };
+                let (l, r) = parse_atom(r, source_map)?;
+                Ok((Expr::UnaryE { op, l: Box::new(l) }, r))
+            }
+            _ => Err(unexpected(f, source_map)),
         },
     }
 }
 
-fn parse_rel_op(tokens: &[Token]) -> Result<(RelOp, &[Token]), io::Error> {
+fn parse_args<'a>(tokens: &'a [Token], source_map: &SourceMap) -> Parsed<'a, Vec<Expr>> {
+    if tokens
+        .first()
+        .is_some_and(|t| t.typ == TokenType::PuncRightParen)
+    {
+        return Ok((vec![], tokens));
+    }
+
+    let mut args = vec![];
+    let mut r = tokens;
+    loop {
+        let (arg, r1) = parse_rel_expr(r, source_map)?;
+        args.push(arg);
+
+        match r1 {
+            [f, r2 @ ..] if f.typ == TokenType::PuncComma => r = r2,
+            _ => return Ok((args, r1)),
+        }
+    }
+}
+
+fn parse_rel_op<'a>(tokens: &'a [Token], source_map: &SourceMap) -> Parsed<'a, RelOp> {
     match tokens {
-        [] => todo!(),
+        [] => Err(end()),
         [f, r @ ..] => match f.typ {
-            TokenType::LeftAngleBracket => match r {
-                [] => todo!(),
-                [s, r @ ..] => match s.typ {
-                    TokenType::Equals => Ok((RelOp::LtEq, r)),
-                    _ => Ok((RelOp::Lt, &tokens[1..])), // include s
-                },
-            },
-            TokenType::RightAngleBracket => match r {
-                [] => todo!(),
-                [s, r @ ..] => match s.typ {
-                    TokenType::Equals => Ok((RelOp::GtEq, r)),
-                    _ => Ok((RelOp::Gt, &tokens[1..])), // include s
-                },
-            },
-            TokenType::Equals => match r {
-                [] => todo!(),
-                [s, r @ ..] => match s.typ {
-                    TokenType::Equals => Ok((RelOp::Eq, r)),
-                    t => Err(io::Error::new(
-                        io::ErrorKind::Other,
-                        format!("token not recognizable {:?}", t),
-                    )),
-                },
-            },
-            TokenType::Bang => match r {
-                [] => todo!(),
-                [s, r @ ..] => match s.typ {
-                    TokenType::Equals => Ok((RelOp::Neq, r)),
-                    t => Err(io::Error::new(
-                        io::ErrorKind::Other,
-                        format!("token not recognizable {:?}", t),
-                    )),
-                },
-            },
-            TokenType::Amp => match r {
-                [] => todo!(),
-                [s, r @ ..] => match s.typ {
-                    TokenType::Amp => Ok((RelOp::And, r)),
-                    t => Err(io::Error::new(
-                        io::ErrorKind::Other,
-                        format!("token not recognizable {:?}", t),
-                    )),
-                },
-            },
-            TokenType::Bar => match r {
-                [] => todo!(),
-                [s, r @ ..] => match s.typ {
-                    TokenType::Bar => Ok((RelOp::Or, r)),
-                    t => Err(io::Error::new(
-                        io::ErrorKind::Other,
-                        format!("token not recognizable {:?}", t),
-                    )),
-                },
-            },
-            t => Err(io::Error::new(
-                io::ErrorKind::Other,
-                format!("token not recognizable {:?}", t),
-            )),
+            TokenType::EqualsEquals => Ok((RelOp::Eq, r)),
+            TokenType::BangEquals => Ok((RelOp::Neq, r)),
+            TokenType::AmpAmp => Ok((RelOp::And, r)),
+            TokenType::BarBar => Ok((RelOp::Or, r)),
+            TokenType::LeftAngleBracketEquals => Ok((RelOp::LtEq, r)),
+            TokenType::LeftAngleBracket => Ok((RelOp::Lt, r)),
+            TokenType::RightAngleBracketEquals => Ok((RelOp::GtEq, r)),
+            TokenType::RightAngleBracket => Ok((RelOp::Gt, r)),
+            _ => Err(unexpected(f, source_map)),
         },
     }
 }
 
-fn parse_term_op(tokens: &[Token]) -> Result<(BinOp, &[Token]), io::Error> {
+fn parse_term_op<'a>(tokens: &'a [Token], source_map: &SourceMap) -> Parsed<'a, BinOp> {
     match tokens {
-        [] => todo!(),
+        [] => Err(end()),
         [f, r @ ..] => match f.typ {
             TokenType::Plus => Ok((BinOp::Add, r)),
             TokenType::Minus => Ok((BinOp::Sub, r)),
-            t => Err(io::Error::new(
-                io::ErrorKind::Other,
-                format!("token not recognizable {:?}", t),
-            )),
+            _ => Err(unexpected(f, source_map)),
         },
     }
 }
 
-fn parse_factor_op(tokens: &[Token]) -> Result<(BinOp, &[Token]), io::Error> {
+fn parse_factor_op<'a>(tokens: &'a [Token], source_map: &SourceMap) -> Parsed<'a, BinOp> {
     match tokens {
-        [] => todo!(),
+        [] => Err(end()),
         [f, r @ ..] => match f.typ {
             TokenType::Star => Ok((BinOp::Mult, r)),
             TokenType::Slash => Ok((BinOp::Div, r)),
-            t => Err(io::Error::new(
-                io::ErrorKind::Other,
-                format!("token not recognizable {:?}", t),
-            )),
+            TokenType::Percent => Ok((BinOp::Mod, r)),
+            _ => Err(unexpected(f, source_map)),
         },
     }
 }
 
-fn mtch(tokens: &[Token], tt: TokenType) -> Result<(&Token, &[Token]), io::Error> {
+fn mtch<'a>(tokens: &'a [Token], tt: TokenType, source_map: &SourceMap) -> Parsed<'a, &'a Token> {
     match tokens {
-        [] => todo!(),
+        [] => Err(end()),
         [f, r @ ..] => {
             if f.typ == tt {
                 // Use an if-guard to compare values
                 Ok((f, r))
             } else {
-                Err(io::Error::new(
-                    io::ErrorKind::Other,
-                    format!("expected: {:?} got: {:?}", tt, f),
-                ))
+                Err(ParseError::Expected {
+                    expected: tt,
+                    found: f.text(source_map).into_owned(),
+                    span: f.span,
+                })
             }
         }
     }
 }
 
+// running out of tokens part way through. only parse knows where the input
+// ends, so it fills in the span
+fn end() -> ParseError {
+    ParseError::UnexpectedEnd {
+        span: Span::default(),
+    }
+}
+
+// the lexer interns every identifier and keyword
+fn symbol(t: &Token) -> Symbol {
+    t.symbol().expect("identifiers and keywords are interned")
+}
+
+fn id(t: &Token) -> Id {
+    Id(symbol(t))
+}
+
+// a token that can't start or continue what is being parsed
+fn unexpected(f: &Token, source_map: &SourceMap) -> ParseError {
+    ParseError::Unexpected {
+        found: f.text(source_map).into_owned(),
+        span: f.span,
+    }
+}
+
+#[cfg(test)]
+pub(crate) fn parse_str(input: &str) -> Result<Program, ParseError> {
+    let mut source_map = SourceMap::new();
+    let file = source_map.add("<test>", input);
+    let tokens = crate::lexer::lex(source_map.file(file).src(), file).unwrap();
+    parse(tokens, &source_map)
+}
+
 #[cfg(test)]
 mod test_legal_arithmetic {
-    use crate::lexer;
     use std::fs;
 
     const TEST_DIR: &str = "tests/fixtures/din/legal/arithmetic";
 
     #[test]
     fn lit() {
-        let chars = fs::read(format!("{TEST_DIR}/lit.c"))
-            .expect("Should have been able to read the file")
-            .iter()
-            .map(|b| *b as char)
-            .collect::<Vec<_>>();
-
-        let tokens = lexer::lex(&chars);
-        let tree = super::parse(tokens).unwrap();
+        let input = fs::read_to_string(format!("{TEST_DIR}/lit.c"))
+            .expect("Should have been able to read the file");
+
+        let tree = super::parse_str(&input).unwrap();
+        insta::assert_yaml_snapshot!(tree, @r###"
+        ---
+        functions:
+          - ret: Int
+            name: main
+            params: []
+            stmts:
+              - Return:
+                  Int: 8
+        "###);
+    }
+
+    #[test]
+    fn lit_hex() {
+        let input = fs::read_to_string(format!("{TEST_DIR}/lit_hex.c"))
+            .expect("Should have been able to read the file");
+
+        let tree = super::parse_str(&input).unwrap();
+        insta::assert_yaml_snapshot!(tree, @r###"
+        ---
+        functions:
+          - ret: Int
+            name: main
+            params: []
+            stmts:
+              - Return:
+                  Int: 31
+        "###);
+    }
+
+    #[test]
+    fn lit_oct() {
+        let input = fs::read_to_string(format!("{TEST_DIR}/lit_oct.c"))
+            .expect("Should have been able to read the file");
+
+        let tree = super::parse_str(&input).unwrap();
+        insta::assert_yaml_snapshot!(tree, @r###"
+        ---
+        functions:
+          - ret: Int
+            name: main
+            params: []
+            stmts:
+              - Return:
+                  Int: 15
+        "###);
+    }
+
+    #[test]
+    fn lit_suffix() {
+        let input = fs::read_to_string(format!("{TEST_DIR}/lit_suffix.c"))
+            .expect("Should have been able to read the file");
+
+        let tree = super::parse_str(&input).unwrap();
         insta::assert_yaml_snapshot!(tree, @r###"
         ---
-        main_function:
-          stmts:
-            - Return:
-                Int: 8
+        functions:
+          - ret: Int
+            name: main
+            params: []
+            stmts:
+              - Return:
+                  BinE:
+                    op: Add
+                    l:
+                      BinE:
+                        op: Add
+                        l:
+                          BinE:
+                            op: Add
+                            l:
+                              ULong: 10
+                            r:
+                              UInt: 4294967295
+                        r:
+                          UInt: 7
+                    r:
+                      ULong: 2147483648
         "###);
     }
 
     #[test]
     fn add() {
-        let chars = fs::read(format!("{TEST_DIR}/add.c"))
-            .expect("Should have been able to read the file")
-            .iter()
-            .map(|b| *b as char)
-            .collect::<Vec<_>>();
-
-        let tokens = lexer::lex(&chars);
-        let tree = super::parse(tokens).unwrap();
+        let input = fs::read_to_string(format!("{TEST_DIR}/add.c"))
+            .expect("Should have been able to read the file");
+
+        let tree = super::parse_str(&input).unwrap();
         insta::assert_yaml_snapshot!(tree, @r###"
         ---
-        main_function:
-          stmts:
-            - Return:
-                BinE:
-                  op: Add
-                  l:
-                    Int: 9
-                  r:
-                    Int: 10
+        functions:
+          - ret: Int
+            name: main
+            params: []
+            stmts:
+              - Return:
+                  BinE:
+                    op: Add
+                    l:
+                      Int: 9
+                    r:
+                      Int: 10
         "###);
     }
 
     #[test]
     fn add_multi() {
-        let chars = fs::read(format!("{TEST_DIR}/add_multi.c"))
-            .expect("Should have been able to read the file")
-            .iter()
-            .map(|b| *b as char)
-            .collect::<Vec<_>>();
-
-        let tokens = lexer::lex(&chars);
-        let tree = super::parse(tokens).unwrap();
+        let input = fs::read_to_string(format!("{TEST_DIR}/add_multi.c"))
+            .expect("Should have been able to read the file");
+
+        let tree = super::parse_str(&input).unwrap();
         insta::assert_yaml_snapshot!(tree, @r###"
         ---
-        main_function:
-          stmts:
-            - Return:
-                BinE:
-                  op: Add
-                  l:
-                    BinE:
-                      op: Add
-                      l:
-                        Int: 9
-                      r:
-                        Int: 10
-                  r:
-                    Int: 11
+        functions:
+          - ret: Int
+            name: main
+            params: []
+            stmts:
+              - Return:
+                  BinE:
+                    op: Add
+                    l:
+                      BinE:
+                        op: Add
+                        l:
+                          Int: 9
+                        r:
+                          Int: 10
+                    r:
+                      Int: 11
         "###);
     }
 
     #[test]
     fn sub() {
         #[rustfmt::skip]
-        let chars = fs::read(format!("{TEST_DIR}/sub.c"))
-            .expect("Should have been able to read the file")
-            .iter()
-            .map(|b| *b as char)
-            .collect::<Vec<_>>();
-
-        let tokens = lexer::lex(&chars);
-        let tree = super::parse(tokens).unwrap();
+        let input = fs::read_to_string(format!("{TEST_DIR}/sub.c"))
+            .expect("Should have been able to read the file");
+
+        let tree = super::parse_str(&input).unwrap();
         insta::assert_yaml_snapshot!(tree, @r###"
         ---
-        main_function:
-          stmts:
-            - Return:
-                BinE:
-                  op: Sub
-                  l:
-                    Int: 88
-                  r:
-                    Int: 32
+        functions:
+          - ret: Int
+            name: main
+            params: []
+            stmts:
+              - Return:
+                  BinE:
+                    op: Sub
+                    l:
+                      Int: 88
+                    r:
+                      Int: 32
         "###);
     }
 
     #[test]
     fn mult() {
         #[rustfmt::skip]
-        let chars = fs::read(format!("{TEST_DIR}/mult.c"))
-            .expect("Should have been able to read the file")
-            .iter()
-            .map(|b| *b as char)
-            .collect::<Vec<_>>();
-
-        let tokens = lexer::lex(&chars);
-        let tree = super::parse(tokens).unwrap();
+        let input = fs::read_to_string(format!("{TEST_DIR}/mult.c"))
+            .expect("Should have been able to read the file");
+
+        let tree = super::parse_str(&input).unwrap();
         insta::assert_yaml_snapshot!(tree, @r###"
         ---
-        main_function:
-          stmts:
-            - Return:
-                BinE:
-                  op: Mult
-                  l:
-                    Int: 9
-                  r:
-                    Int: 10
+        functions:
+          - ret: Int
+            name: main
+            params: []
+            stmts:
+              - Return:
+                  BinE:
+                    op: Mult
+                    l:
+                      Int: 9
+                    r:
+                      Int: 10
         "###);
     }
 
     #[test]
     fn div() {
         #[rustfmt::skip]
-        let chars = fs::read(format!("{TEST_DIR}/div.c"))
-            .expect("Should have been able to read the file")
-            .iter()
-            .map(|b| *b as char)
-            .collect::<Vec<_>>();
-
-        let tokens = lexer::lex(&chars);
-        let tree = super::parse(tokens).unwrap();
+        let input = fs::read_to_string(format!("{TEST_DIR}/div.c"))
+            .expect("Should have been able to read the file");
+
+        let tree = super::parse_str(&input).unwrap();
         insta::assert_yaml_snapshot!(tree, @r###"
         ---
-        main_function:
-          stmts:
-            - Return:
-                BinE:
-                  op: Div
-                  l:
-                    Int: 100
-                  r:
-                    Int: 9
+        functions:
+          - ret: Int
+            name: main
+            params: []
+            stmts:
+              - Return:
+                  BinE:
+                    op: Div
+                    l:
+                      Int: 100
+                    r:
+                      Int: 9
+        "###);
+    }
+
+    #[test]
+    fn neg() {
+        let input = fs::read_to_string(format!("{TEST_DIR}/neg.c"))
+            .expect("Should have been able to read the file");
+
+        let tree = super::parse_str(&input).unwrap();
+        insta::assert_yaml_snapshot!(tree.functions[0].stmts[0], @r###"
+        ---
+        Return:
+          BinE:
+            op: Add
+            l:
+              BinE:
+                op: Mult
+                l:
+                  UnaryE:
+                    op: Sub
+                    l:
+                      Int: 3
+                r:
+                  UnaryE:
+                    op: Sub
+                    l:
+                      Int: 4
+            r:
+              UnaryE:
+                op: Add
+                l:
+                  Int: 2
         "###);
     }
 }
 
 #[cfg(test)]
 mod test_legal_arithmetic_precedence {
-    use crate::lexer;
     use std::fs;
 
     const TEST_DIR: &str = "tests/fixtures/din/legal/arithmetic_precedence";
 
     #[test]
     fn add_associative() {
-        let chars = fs::read(format!("{TEST_DIR}/add_associative.c"))
-            .expect("Should have been able to read the file")
-            .iter()
-            .map(|b| *b as char)
-            .collect::<Vec<_>>();
-
-        let tokens = lexer::lex(&chars);
-        let tree = super::parse(tokens).unwrap();
+        let input = fs::read_to_string(format!("{TEST_DIR}/add_associative.c"))
+            .expect("Should have been able to read the file");
+
+        let tree = super::parse_str(&input).unwrap();
         insta::assert_yaml_snapshot!(tree, @r###"
         ---
-        main_function:
-          stmts:
-            - Return:
-                BinE:
-                  op: Add
-                  l:
-                    BinE:
-                      op: Add
-                      l:
-                        Int: 9
-                      r:
-                        Int: 10
-                  r:
-                    Int: 11
-        "###);
-    }
+        functions:
+          - ret: Int
+            name: main
+            params: []
+            stmts:
+              - Return:
+                  BinE:
+                    op: Add
+                    l:
+                      BinE:
+                        op: Add
+                        l:
+                          Int: 9
+                        r:
+                          Int: 10
+                    r:
+                      Int: 11
+        "###);
+    }
 
     #[test]
     fn sub_associative() {
-        let chars = fs::read(format!("{TEST_DIR}/sub_associative.c"))
-            .expect("Should have been able to read the file")
-            .iter()
-            .map(|b| *b as char)
-            .collect::<Vec<_>>();
-
-        let tokens = lexer::lex(&chars);
-        let tree = super::parse(tokens).unwrap();
+        let input = fs::read_to_string(format!("{TEST_DIR}/sub_associative.c"))
+            .expect("Should have been able to read the file");
+
+        let tree = super::parse_str(&input).unwrap();
         insta::assert_yaml_snapshot!(tree, @r###"
         ---
-        main_function:
-          stmts:
-            - Return:
-                BinE:
-                  op: Sub
-                  l:
-                    BinE:
-                      op: Sub
-                      l:
-                        Int: 30
-                      r:
-                        Int: 9
-                  r:
-                    Int: 10
+        functions:
+          - ret: Int
+            name: main
+            params: []
+            stmts:
+              - Return:
+                  BinE:
+                    op: Sub
+                    l:
+                      BinE:
+                        op: Sub
+                        l:
+                          Int: 30
+                        r:
+                          Int: 9
+                    r:
+                      Int: 10
         "###);
     }
 
     #[test]
     fn mult_add_precedence() {
-        let chars = fs::read(format!("{TEST_DIR}/mult_add_precedence.c"))
-            .expect("Should have been able to read the file")
-            .iter()
-            .map(|b| *b as char)
-            .collect::<Vec<_>>();
-
-        let tokens = lexer::lex(&chars);
-        let tree = super::parse(tokens).unwrap();
+        let input = fs::read_to_string(format!("{TEST_DIR}/mult_add_precedence.c"))
+            .expect("Should have been able to read the file");
+
+        let tree = super::parse_str(&input).unwrap();
         insta::assert_yaml_snapshot!(tree, @r###"
         ---
-        main_function:
-          stmts:
-            - Return:
-                BinE:
-                  op: Add
-                  l:
-                    BinE:
-                      op: Mult
-                      l:
-                        Int: 9
-                      r:
-                        Int: 10
-                  r:
-                    Int: 11
+        functions:
+          - ret: Int
+            name: main
+            params: []
+            stmts:
+              - Return:
+                  BinE:
+                    op: Add
+                    l:
+                      BinE:
+                        op: Mult
+                        l:
+                          Int: 9
+                        r:
+                          Int: 10
+                    r:
+                      Int: 11
         "###);
     }
 
     #[test]
     fn mult_add_precedence_multi() {
-        let chars = fs::read(format!("{TEST_DIR}/mult_add_precedence_multi.c"))
-            .expect("Should have been able to read the file")
-            .iter()
-            .map(|b| *b as char)
-            .collect::<Vec<_>>();
-
-        let tokens = lexer::lex(&chars);
-        let tree = super::parse(tokens).unwrap();
+        let input = fs::read_to_string(format!("{TEST_DIR}/mult_add_precedence_multi.c"))
+            .expect("Should have been able to read the file");
+
+        let tree = super::parse_str(&input).unwrap();
         insta::assert_yaml_snapshot!(tree, @r###"
         ---
-        main_function:
-          stmts:
-            - Return:
-                BinE:
-                  op: Add
-                  l:
-                    BinE:
-                      op: Mult
-                      l:
-                        Int: 9
-                      r:
-                        Int: 10
-                  r:
-                    BinE:
-                      op: Mult
-                      l:
-                        Int: 11
-                      r:
-                        Int: 12
+        functions:
+          - ret: Int
+            name: main
+            params: []
+            stmts:
+              - Return:
+                  BinE:
+                    op: Add
+                    l:
+                      BinE:
+                        op: Mult
+                        l:
+                          Int: 9
+                        r:
+                          Int: 10
+                    r:
+                      BinE:
+                        op: Mult
+                        l:
+                          Int: 11
+                        r:
+                          Int: 12
         "###);
     }
 }
 
 #[cfg(test)]
 mod test_legal_control_flow {
-    use crate::lexer;
     use std::fs;
 
     const TEST_DIR: &str = "tests/fixtures/din/legal/control_flow";
 
     #[test]
     fn eq() {
-        let chars = fs::read(format!("{TEST_DIR}/eq_true.c"))
-            .expect("Should have been able to read the file")
-            .iter()
-            .map(|b| *b as char)
-            .collect::<Vec<_>>();
-
-        let tokens = lexer::lex(&chars);
-        let tree = super::parse(tokens).unwrap();
+        let input = fs::read_to_string(format!("{TEST_DIR}/eq_true.c"))
+            .expect("Should have been able to read the file");
+
+        let tree = super::parse_str(&input).unwrap();
         insta::assert_yaml_snapshot!(tree, @r###"
         ---
-        main_function:
-          stmts:
-            - Return:
-                RelE:
-                  op: Eq
-                  l:
-                    Int: 9
-                  r:
-                    Int: 9
+        functions:
+          - ret: Int
+            name: main
+            params: []
+            stmts:
+              - Return:
+                  RelE:
+                    op: Eq
+                    l:
+                      Int: 9
+                    r:
+                      Int: 9
         "###);
     }
 
     #[test]
     fn neq() {
-        let chars = fs::read(format!("{TEST_DIR}/neq_true.c"))
-            .expect("Should have been able to read the file")
-            .iter()
-            .map(|b| *b as char)
-            .collect::<Vec<_>>();
-
-        let tokens = lexer::lex(&chars);
-        let tree = super::parse(tokens).unwrap();
+        let input = fs::read_to_string(format!("{TEST_DIR}/neq_true.c"))
+            .expect("Should have been able to read the file");
+
+        let tree = super::parse_str(&input).unwrap();
         insta::assert_yaml_snapshot!(tree, @r###"
         ---
-        main_function:
-          stmts:
-            - Return:
-                RelE:
-                  op: Neq
-                  l:
-                    Int: 9
-                  r:
-                    Int: 10
+        functions:
+          - ret: Int
+            name: main
+            params: []
+            stmts:
+              - Return:
+                  RelE:
+                    op: Neq
+                    l:
+                      Int: 9
+                    r:
+                      Int: 10
         "###);
     }
 
     #[test]
     fn and() {
-        let chars = fs::read(format!("{TEST_DIR}/and_true.c"))
-            .expect("Should have been able to read the file")
-            .iter()
-            .map(|b| *b as char)
-            .collect::<Vec<_>>();
-
-        let tokens = lexer::lex(&chars);
-        let tree = super::parse(tokens).unwrap();
+        let input = fs::read_to_string(format!("{TEST_DIR}/and_true.c"))
+            .expect("Should have been able to read the file");
+
+        let tree = super::parse_str(&input).unwrap();
         insta::assert_yaml_snapshot!(tree, @r###"
         ---
-        main_function:
-          stmts:
-            - Return:
-                RelE:
-                  op: And
-                  l:
-                    Int: 1
-                  r:
-                    Int: 1
+        functions:
+          - ret: Int
+            name: main
+            params: []
+            stmts:
+              - Return:
+                  RelE:
+                    op: And
+                    l:
+                      Int: 1
+                    r:
+                      Int: 1
         "###);
     }
 
     #[test]
     fn or() {
-        let chars = fs::read(format!("{TEST_DIR}/or_true.c"))
-            .expect("Should have been able to read the file")
-            .iter()
-            .map(|b| *b as char)
-            .collect::<Vec<_>>();
-
-        let tokens = lexer::lex(&chars);
-        let tree = super::parse(tokens).unwrap();
+        let input = fs::read_to_string(format!("{TEST_DIR}/or_true.c"))
+            .expect("Should have been able to read the file");
+
+        let tree = super::parse_str(&input).unwrap();
         insta::assert_yaml_snapshot!(tree, @r###"
         ---
-        main_function:
-          stmts:
-            - Return:
-                RelE:
-                  op: Or
-                  l:
-                    Int: 1
-                  r:
-                    Int: 1
+        functions:
+          - ret: Int
+            name: main
+            params: []
+            stmts:
+              - Return:
+                  RelE:
+                    op: Or
+                    l:
+                      Int: 1
+                    r:
+                      Int: 1
         "###);
     }
 
     #[test]
     fn lt() {
-        let chars = fs::read(format!("{TEST_DIR}/lt_true.c"))
-            .expect("Should have been able to read the file")
-            .iter()
-            .map(|b| *b as char)
-            .collect::<Vec<_>>();
-
-        let tokens = lexer::lex(&chars);
-        let tree = super::parse(tokens).unwrap();
+        let input = fs::read_to_string(format!("{TEST_DIR}/lt_true.c"))
+            .expect("Should have been able to read the file");
+
+        let tree = super::parse_str(&input).unwrap();
         insta::assert_yaml_snapshot!(tree, @r###"
         ---
-        main_function:
-          stmts:
-            - Return:
-                RelE:
-                  op: Lt
-                  l:
-                    Int: 9
-                  r:
-                    Int: 10
+        functions:
+          - ret: Int
+            name: main
+            params: []
+            stmts:
+              - Return:
+                  RelE:
+                    op: Lt
+                    l:
+                      Int: 9
+                    r:
+                      Int: 10
         "###);
     }
 
     #[test]
     fn gt() {
-        let chars = fs::read(format!("{TEST_DIR}/gt_true.c"))
-            .expect("Should have been able to read the file")
-            .iter()
-            .map(|b| *b as char)
-            .collect::<Vec<_>>();
-
-        let tokens = lexer::lex(&chars);
-        let tree = super::parse(tokens).unwrap();
+        let input = fs::read_to_string(format!("{TEST_DIR}/gt_true.c"))
+            .expect("Should have been able to read the file");
+
+        let tree = super::parse_str(&input).unwrap();
         insta::assert_yaml_snapshot!(tree, @r###"
         ---
-        main_function:
-          stmts:
-            - Return:
-                RelE:
-                  op: Gt
-                  l:
-                    Int: 10
-                  r:
-                    Int: 9
+        functions:
+          - ret: Int
+            name: main
+            params: []
+            stmts:
+              - Return:
+                  RelE:
+                    op: Gt
+                    l:
+                      Int: 10
+                    r:
+                      Int: 9
         "###);
     }
 
     #[test]
     fn ifels_then() {
-        let chars = fs::read(format!("{TEST_DIR}/ifels_then.c"))
-            .expect("Should have been able to read the file")
-            .iter()
-            .map(|b| *b as char)
-            .collect::<Vec<_>>();
-
-        let tokens = lexer::lex(&chars);
-        let tree = super::parse(tokens).unwrap();
+        let input = fs::read_to_string(format!("{TEST_DIR}/ifels_then.c"))
+            .expect("Should have been able to read the file");
+
+        let tree = super::parse_str(&input).unwrap();
         insta::assert_yaml_snapshot!(tree, @r###"
         ---
-        main_function:
-          stmts:
-            - IfEls:
-                cond:
-                  RelE:
-                    op: Lt
-                    l:
-                      Int: 9
-                    r:
-                      Int: 10
-                then:
-                  Return:
-                    Int: 0
-                els:
-                  Return:
-                    Int: 1
+        functions:
+          - ret: Int
+            name: main
+            params: []
+            stmts:
+              - IfEls:
+                  cond:
+                    RelE:
+                      op: Lt
+                      l:
+                        Int: 9
+                      r:
+                        Int: 10
+                  then:
+                    Block:
+                      - Return:
+                          Int: 0
+                  els:
+                    Block:
+                      - Return:
+                          Int: 1
         "###);
     }
 
     #[test]
     fn for_loop() {
-        let chars = fs::read(format!("{TEST_DIR}/for.c"))
-            .expect("Should have been able to read the file")
-            .iter()
-            .map(|b| *b as char)
-            .collect::<Vec<_>>();
-
-        let tokens = lexer::lex(&chars);
-        let tree = super::parse(tokens).unwrap();
+        let input = fs::read_to_string(format!("{TEST_DIR}/for.c"))
+            .expect("Should have been able to read the file");
+
+        let tree = super::parse_str(&input).unwrap();
         insta::assert_yaml_snapshot!(tree, @r###"
         ---
-        main_function:
-          stmts:
-            - Asnmt:
-                CreateBind:
-                  id: n
-                  expr:
-                    Int: 0
-            - For:
-                asnmt:
+        functions:
+          - ret: Int
+            name: main
+            params: []
+            stmts:
+              - Asnmt:
+                  CreateBind:
+                    id: n
+                    expr:
+                      Int: 0
+              - For:
+                  asnmt:
+                    CreateBind:
+                      id: i
+                      expr:
+                        Int: 0
+                  cond:
+                    RelE:
+                      op: Lt
+                      l:
+                        Var: i
+                      r:
+                        Int: 10
+                  update:
+                    UpdateBind:
+                      id: i
+                      op: Add
+                      expr:
+                        Int: 1
+                  body:
+                    Block:
+                      - Asnmt:
+                          UpdateBind:
+                            id: n
+                            op: Add
+                            expr:
+                              Int: 1
+                      - Asnmt:
+                          UpdateBind:
+                            id: n
+                            op: Add
+                            expr:
+                              Int: 1
+              - Return:
+                  Var: n
+        "###);
+    }
+
+    #[test]
+    fn while_loop() {
+        let input = fs::read_to_string(format!("{TEST_DIR}/while.c"))
+            .expect("Should have been able to read the file");
+
+        let tree = super::parse_str(&input).unwrap();
+        insta::assert_yaml_snapshot!(tree, @r###"
+        ---
+        functions:
+          - ret: Int
+            name: main
+            params: []
+            stmts:
+              - Asnmt:
+                  CreateBind:
+                    id: n
+                    expr:
+                      Int: 0
+              - Asnmt:
                   CreateBind:
                     id: i
                     expr:
                       Int: 0
+              - While:
+                  cond:
+                    RelE:
+                      op: Lt
+                      l:
+                        Var: i
+                      r:
+                        Int: 10
+                  body:
+                    Block:
+                      - Asnmt:
+                          UpdateBind:
+                            id: n
+                            op: Add
+                            expr:
+                              Var: i
+                      - Asnmt:
+                          UpdateBind:
+                            id: i
+                            op: Add
+                            expr:
+                              Int: 1
+              - Return:
+                  Var: n
+        "###);
+    }
+
+    #[test]
+    fn do_while() {
+        let input = fs::read_to_string(format!("{TEST_DIR}/do_while.c"))
+            .expect("Should have been able to read the file");
+
+        let tree = super::parse_str(&input).unwrap();
+        insta::assert_yaml_snapshot!(tree, @r###"
+        ---
+        functions:
+          - ret: Int
+            name: main
+            params: []
+            stmts:
+              - Asnmt:
+                  CreateBind:
+                    id: n
+                    expr:
+                      Int: 0
+              - DoWhile:
+                  body:
+                    Block:
+                      - Asnmt:
+                          UpdateBind:
+                            id: n
+                            op: Add
+                            expr:
+                              Int: 7
+                  cond:
+                    RelE:
+                      op: Lt
+                      l:
+                        Var: n
+                      r:
+                        Int: 40
+              - Return:
+                  Var: n
+        "###);
+    }
+
+    #[test]
+    fn while_unbraced() {
+        let input = fs::read_to_string(format!("{TEST_DIR}/while_unbraced.c"))
+            .expect("Should have been able to read the file");
+
+        let tree = super::parse_str(&input).unwrap();
+        insta::assert_yaml_snapshot!(tree.functions[0].stmts[1], @r###"
+        ---
+        While:
+          cond:
+            RelE:
+              op: Lt
+              l:
+                Var: i
+              r:
+                Int: 3
+          body:
+            Asnmt:
+              UpdateBind:
+                id: i
+                op: Add
+                expr:
+                  Int: 1
+        "###);
+    }
+
+    #[test]
+    fn do_while_unbraced() {
+        let input = fs::read_to_string(format!("{TEST_DIR}/do_while_unbraced.c"))
+            .expect("Should have been able to read the file");
+
+        let tree = super::parse_str(&input).unwrap();
+        insta::assert_yaml_snapshot!(tree.functions[0].stmts[1], @r###"
+        ---
+        DoWhile:
+          body:
+            Asnmt:
+              UpdateBind:
+                id: x
+                op: Add
+                expr:
+                  Int: 7
+          cond:
+            RelE:
+              op: Lt
+              l:
+                Var: x
+              r:
+                Int: 40
+        "###);
+    }
+
+    #[test]
+    fn for_unbraced() {
+        let input = fs::read_to_string(format!("{TEST_DIR}/for_unbraced.c"))
+            .expect("Should have been able to read the file");
+
+        let tree = super::parse_str(&input).unwrap();
+        insta::assert_yaml_snapshot!(tree.functions[0].stmts[2], @r###"
+        ---
+        For:
+          asnmt:
+            Bind:
+              id: i
+              expr:
+                Int: 0
+          cond:
+            RelE:
+              op: Lt
+              l:
+                Var: i
+              r:
+                Int: 3
+          update:
+            UpdateBind:
+              id: i
+              op: Add
+              expr:
+                Int: 1
+          body:
+            Asnmt:
+              UpdateBind:
+                id: n
+                op: Add
+                expr:
+                  Int: 1
+        "###);
+    }
+
+    #[test]
+    fn for_empty() {
+        let input = fs::read_to_string(format!("{TEST_DIR}/for_empty.c"))
+            .expect("Should have been able to read the file");
+
+        let tree = super::parse_str(&input).unwrap();
+        insta::assert_yaml_snapshot!(tree.functions[0].stmts[1], @r###"
+        ---
+        For:
+          asnmt: ~
+          cond:
+            RelE:
+              op: Lt
+              l:
+                Var: n
+              r:
+                Int: 42
+          update: ~
+          body:
+            Block:
+              - Asnmt:
+                  UpdateBind:
+                    id: n
+                    op: Add
+                    expr:
+                      Int: 6
+        "###);
+    }
+
+    #[test]
+    fn break_continue() {
+        let input = fs::read_to_string(format!("{TEST_DIR}/break.c"))
+            .expect("Should have been able to read the file");
+
+        let tree = super::parse_str(&input).unwrap();
+        insta::assert_yaml_snapshot!(tree.functions[0].stmts[1], @r###"
+        ---
+        While:
+          cond:
+            Int: 1
+          body:
+            Block:
+              - Asnmt:
+                  UpdateBind:
+                    id: n
+                    op: Add
+                    expr:
+                      Int: 1
+              - IfEls:
+                  cond:
+                    RelE:
+                      op: Lt
+                      l:
+                        Var: n
+                      r:
+                        Int: 42
+                  then:
+                    Block:
+                      - Continue
+                  els:
+                    Block:
+                      - Break
+        "###);
+    }
+
+    #[test]
+    fn switch() {
+        let input = fs::read_to_string(format!("{TEST_DIR}/switch.c"))
+            .expect("Should have been able to read the file");
+
+        let tree = super::parse_str(&input).unwrap();
+        insta::assert_yaml_snapshot!(tree.functions[0].stmts[1], @r###"
+        ---
+        For:
+          asnmt:
+            CreateBind:
+              id: i
+              expr:
+                Int: 0
+          cond:
+            RelE:
+              op: Lt
+              l:
+                Var: i
+              r:
+                Int: 4
+          update:
+            UpdateBind:
+              id: i
+              op: Add
+              expr:
+                Int: 1
+          body:
+            Block:
+              - Switch:
+                  cond:
+                    Var: i
+                  body:
+                    Block:
+                      - Case:
+                          value:
+                            Int: 0
+                          stmt:
+                            Asnmt:
+                              UpdateBind:
+                                id: n
+                                op: Add
+                                expr:
+                                  Int: 1
+                      - Case:
+                          value:
+                            Int: 1
+                          stmt:
+                            Asnmt:
+                              UpdateBind:
+                                id: n
+                                op: Add
+                                expr:
+                                  Int: 10
+                      - Break
+                      - Default:
+                          Asnmt:
+                            UpdateBind:
+                              id: n
+                              op: Add
+                              expr:
+                                Int: 100
+        "###);
+    }
+
+    #[test]
+    fn switch_unbraced() {
+        let input = fs::read_to_string(format!("{TEST_DIR}/switch_unbraced.c"))
+            .expect("Should have been able to read the file");
+
+        let tree = super::parse_str(&input).unwrap();
+        insta::assert_yaml_snapshot!(&tree.functions[0].stmts[1..], @r###"
+        ---
+        - Switch:
+            cond:
+              Var: n
+            body:
+              Case:
+                value:
+                  Int: 0
+                stmt:
+                  Asnmt:
+                    Bind:
+                      id: n
+                      expr:
+                        Int: 42
+        - Return:
+            Var: n
+        "###);
+    }
+
+    #[test]
+    fn else_if() {
+        let input = fs::read_to_string(format!("{TEST_DIR}/else_if.c"))
+            .expect("Should have been able to read the file");
+
+        let tree = super::parse_str(&input).unwrap();
+        insta::assert_yaml_snapshot!(tree.functions[0].stmts, @r###"
+        ---
+        - IfEls:
+            cond:
+              RelE:
+                op: Lt
+                l:
+                  Var: x
+                r:
+                  Int: 10
+            then:
+              Return:
+                Int: 1
+            els:
+              IfEls:
                 cond:
                   RelE:
                     op: Lt
                     l:
-                      Var: i
+                      Var: x
                     r:
-                      Int: 10
-                update:
+                      Int: 20
+                then:
+                  Return:
+                    Int: 2
+                els:
+                  IfEls:
+                    cond:
+                      RelE:
+                        op: Lt
+                        l:
+                          Var: x
+                        r:
+                          Int: 30
+                    then:
+                      Return:
+                        Int: 3
+                    els:
+                      Return:
+                        Int: 4
+        "###);
+    }
+
+    #[test]
+    fn dangling_else() {
+        let input = fs::read_to_string(format!("{TEST_DIR}/dangling_else.c"))
+            .expect("Should have been able to read the file");
+
+        let tree = super::parse_str(&input).unwrap();
+        insta::assert_yaml_snapshot!(tree.functions[0].stmts[1], @r###"
+        ---
+        If:
+          cond:
+            Int: 1
+          then:
+            IfEls:
+              cond:
+                Int: 0
+              then:
+                Asnmt:
                   UpdateBind:
-                    id: i
+                    id: n
                     op: Add
                     expr:
                       Int: 1
-                body:
-                  - Asnmt:
-                      UpdateBind:
-                        id: n
-                        op: Add
-                        expr:
-                          Int: 1
-                  - Asnmt:
-                      UpdateBind:
-                        id: n
-                        op: Add
-                        expr:
-                          Int: 1
-            - Return:
-                Var: n
+              els:
+                Asnmt:
+                  UpdateBind:
+                    id: n
+                    op: Add
+                    expr:
+                      Int: 42
+        "###);
+    }
+
+    #[test]
+    fn block() {
+        let input = fs::read_to_string(format!("{TEST_DIR}/block.c"))
+            .expect("Should have been able to read the file");
+
+        let tree = super::parse_str(&input).unwrap();
+        insta::assert_yaml_snapshot!(&tree.functions[0].stmts[1..3], @r###"
+        ---
+        - Block:
+            - Asnmt:
+                CreateBind:
+                  id: n
+                  expr:
+                    Int: 1
+            - Asnmt:
+                UpdateBind:
+                  id: n
+                  op: Add
+                  expr:
+                    Int: 1
+        - Block:
+            - Asnmt:
+                UpdateBind:
+                  id: n
+                  op: Add
+                  expr:
+                    Int: 2
         "###);
     }
 }
 
 #[cfg(test)]
 mod test_legal_data_flow {
-    use crate::lexer;
     use std::fs;
 
     const TEST_DIR: &str = "tests/fixtures/din/legal/data_flow";
 
     #[test]
     fn asnmt() {
-        let chars = fs::read(format!("{TEST_DIR}/asnmt.c"))
-            .expect("Should have been able to read the file")
-            .iter()
-            .map(|b| *b as char)
-            .collect::<Vec<_>>();
-
-        let tokens = lexer::lex(&chars);
-        let tree = super::parse(tokens).unwrap();
+        let input = fs::read_to_string(format!("{TEST_DIR}/asnmt.c"))
+            .expect("Should have been able to read the file");
+
+        let tree = super::parse_str(&input).unwrap();
         insta::assert_yaml_snapshot!(tree, @r###"
         ---
-        main_function:
-          stmts:
-            - Asnmt:
-                CreateBind:
-                  id: x
-                  expr:
-                    Int: 8
-            - Return:
-                Var: x
+        functions:
+          - ret: Int
+            name: main
+            params: []
+            stmts:
+              - Asnmt:
+                  CreateBind:
+                    id: x
+                    expr:
+                      Int: 8
+              - Return:
+                  Var: x
         "###);
     }
 
     #[test]
     fn asnmt_update() {
-        let chars = fs::read(format!("{TEST_DIR}/asnmt_update.c"))
-            .expect("Should have been able to read the file")
-            .iter()
-            .map(|b| *b as char)
-            .collect::<Vec<_>>();
-
-        let tokens = lexer::lex(&chars);
-        let tree = super::parse(tokens).unwrap();
+        let input = fs::read_to_string(format!("{TEST_DIR}/asnmt_update.c"))
+            .expect("Should have been able to read the file");
+
+        let tree = super::parse_str(&input).unwrap();
         insta::assert_yaml_snapshot!(tree, @r###"
         ---
-        main_function:
-          stmts:
-            - Asnmt:
-                CreateBind:
-                  id: n
-                  expr:
-                    Int: 0
-            - Asnmt:
-                UpdateBind:
-                  id: n
-                  op: Add
-                  expr:
-                    Int: 10
-            - Return:
-                Var: n
+        functions:
+          - ret: Int
+            name: main
+            params: []
+            stmts:
+              - Asnmt:
+                  CreateBind:
+                    id: n
+                    expr:
+                      Int: 0
+              - Asnmt:
+                  UpdateBind:
+                    id: n
+                    op: Add
+                    expr:
+                      Int: 10
+              - Return:
+                  Var: n
         "###);
     }
 }
@@ -1120,3 +1991,268 @@ mod test_legal_data_flow {
 //         let _ = parse_program(tokens);
 //     }
 // }
+
+#[cfg(test)]
+mod test_legal_literals {
+    use std::fs;
+
+    const TEST_DIR: &str = "tests/fixtures/din/legal/literals";
+
+    #[test]
+    fn char() {
+        let input = fs::read_to_string(format!("{TEST_DIR}/char.c"))
+            .expect("Should have been able to read the file");
+
+        let tree = super::parse_str(&input).unwrap();
+        insta::assert_yaml_snapshot!(tree, @r###"
+        ---
+        functions:
+          - ret: Int
+            name: main
+            params: []
+            stmts:
+              - Return:
+                  BinE:
+                    op: Sub
+                    l:
+                      BinE:
+                        op: Add
+                        l:
+                          BinE:
+                            op: Sub
+                            l:
+                              BinE:
+                                op: Add
+                                l:
+                                  BinE:
+                                    op: Sub
+                                    l:
+                                      Char: 97
+                                    r:
+                                      Char: 10
+                                r:
+                                  Char: 65
+                            r:
+                              Char: 65
+                        r:
+                          Char: 92
+                    r:
+                      Int: 92
+        "###);
+    }
+
+    #[test]
+    fn str() {
+        let input = fs::read_to_string(format!("{TEST_DIR}/str.c"))
+            .expect("Should have been able to read the file");
+
+        let tree = super::parse_str(&input).unwrap();
+        insta::assert_yaml_snapshot!(tree, @r###"
+        ---
+        functions:
+          - ret: Int
+            name: main
+            params: []
+            stmts:
+              - Return:
+                  Str:
+                    - 104
+                    - 101
+                    - 108
+                    - 108
+                    - 111
+                    - 44
+                    - 32
+                    - 119
+                    - 111
+                    - 114
+                    - 108
+                    - 100
+                    - 10
+                    - 9
+                    - 34
+                    - 0
+        "###);
+    }
+
+    #[test]
+    fn float() {
+        let input = fs::read_to_string(format!("{TEST_DIR}/float.c"))
+            .expect("Should have been able to read the file");
+
+        let tree = super::parse_str(&input).unwrap();
+        insta::assert_yaml_snapshot!(tree, @r###"
+        ---
+        functions:
+          - ret: Int
+            name: main
+            params: []
+            stmts:
+              - Return:
+                  BinE:
+                    op: Add
+                    l:
+                      BinE:
+                        op: Add
+                        l:
+                          BinE:
+                            op: Add
+                            l:
+                              Double: 1.5
+                            r:
+                              Double: 0.0005
+                        r:
+                          Float: 2
+                    r:
+                      LongDouble: 10000000000
+        "###);
+    }
+}
+
+#[cfg(test)]
+mod test_legal_functions {
+    use std::fs;
+
+    const TEST_DIR: &str = "tests/fixtures/din/legal/functions";
+
+    #[test]
+    fn definitions() {
+        let input = fs::read_to_string(format!("{TEST_DIR}/definitions.c"))
+            .expect("Should have been able to read the file");
+
+        let tree = super::parse_str(&input).unwrap();
+        insta::assert_yaml_snapshot!(tree, @r###"
+        ---
+        functions:
+          - ret: Int
+            name: add
+            params:
+              - typ: Int
+                id: a
+              - typ: Int
+                id: b
+            stmts:
+              - Return:
+                  BinE:
+                    op: Add
+                    l:
+                      Var: a
+                    r:
+                      Var: b
+          - ret: ULong
+            name: scale
+            params:
+              - typ: UInt
+                id: x
+              - typ:
+                  Pointer: SChar
+                id: s
+              - typ:
+                  Pointer:
+                    Pointer: Double
+                id: m
+            stmts:
+              - Return:
+                  Var: x
+          - ret: Void
+            name: nothing
+            params: []
+            stmts: []
+          - ret: Int
+            name: main
+            params: []
+            stmts:
+              - Return:
+                  Int: 0
+        "###);
+    }
+
+    #[test]
+    fn calls() {
+        let input = fs::read_to_string(format!("{TEST_DIR}/calls.c"))
+            .expect("Should have been able to read the file");
+
+        let tree = super::parse_str(&input).unwrap();
+        insta::assert_yaml_snapshot!(tree.functions[3], @r###"
+        ---
+        ret: Int
+        name: main
+        params: []
+        stmts:
+          - Expr:
+              Call:
+                id: nothing
+                args: []
+          - Return:
+              BinE:
+                op: Sub
+                l:
+                  Call:
+                    id: add
+                    args:
+                      - Call:
+                          id: sum
+                          args:
+                            - Int: 1
+                            - Int: 2
+                            - Int: 3
+                            - Int: 4
+                            - Int: 5
+                            - Int: 6
+                            - Int: 7
+                            - Int: 8
+                            - Int: 9
+                            - Int: 10
+                      - Int: 2
+                r:
+                  Int: 15
+        "###);
+    }
+}
+
+#[cfg(test)]
+mod test_illegal {
+
+    #[test]
+    fn split_eq() {
+        let input = "int main() { return 1 = = 1; }";
+
+        assert!(super::parse_str(input).is_err());
+    }
+
+    #[test]
+    fn unexpected_token() {
+        let input = "int main() { return 1 +; }";
+
+        let err = super::parse_str(input).unwrap_err();
+        assert_eq!(err.to_string(), "unexpected ';'");
+        assert_eq!(err.span().lo, 23);
+    }
+
+    #[test]
+    fn truncated() {
+        let input = "int main() { return 0;";
+
+        let err = super::parse_str(input).unwrap_err();
+        assert_eq!(err.to_string(), "unexpected end of input");
+        assert_eq!(err.span().lo, input.len());
+    }
+
+    #[test]
+    fn unnamed_param() {
+        let input = "int f(int) { return 1; }";
+
+        assert!(super::parse_str(input).is_err());
+    }
+
+    #[test]
+    fn type_specifiers() {
+        let input = "long char f() { return 1; }";
+
+        let err = super::parse_str(input).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "invalid combination of type specifiers: char long"
+        );
+    }
+}
@@ -0,0 +1,2351 @@
+use crate::{
+    lexer::{self, LexError, Lexeme, Lexer, Std, Token, TokenType},
+    source::{FileId, SourceMap, Span},
+    splice::splice,
+    symbol::Symbol,
+    target::Target,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+use thiserror::Error;
+
+#[derive(Error, Clone, PartialEq, Serialize, Deserialize, Debug)]
+pub enum PreprocessError {
+    #[error(transparent)]
+    Lex(#[from] LexError),
+    #[error("invalid preprocessing directive #{name}")]
+    UnknownDirective { name: String, span: Span },
+    #[error("#include expects \"FILENAME\" or <FILENAME>")]
+    MalformedInclude { span: Span },
+    #[error("{name}: No such file or directory")]
+    IncludeNotFound { name: String, span: Span },
+    #[error("{name}: {reason}")]
+    Unreadable {
+        name: String,
+        reason: String,
+        span: Span,
+    },
+    #[error("#include cycle: {name} includes itself")]
+    IncludeCycle { name: String, span: Span },
+    #[error("macro names must be identifiers")]
+    MacroNameMissing { span: Span },
+    #[error("invalid macro parameter list")]
+    InvalidMacroParams { span: Span },
+    #[error("duplicate macro parameter {name}")]
+    DuplicateMacroParam { name: String, span: Span },
+    #[error("'#' is not followed by a macro parameter")]
+    StringifyNonParam { span: Span },
+    #[error("'##' cannot appear at either end of a macro expansion")]
+    PasteAtEdge { span: Span },
+    #[error("{name} redefined")]
+    MacroRedefined { name: String, span: Span },
+    #[error("pasting {lhs} and {rhs} does not give a valid preprocessing token")]
+    InvalidPaste {
+        lhs: String,
+        rhs: String,
+        span: Span,
+    },
+    #[error("unterminated argument list invoking macro {name}")]
+    UnterminatedInvocation { name: String, span: Span },
+    #[error("macro {name} requires {expected} arguments, but {got} given")]
+    ArgCount {
+        name: String,
+        expected: usize,
+        got: usize,
+        span: Span,
+    },
+    #[error("unterminated #{name}")]
+    UnterminatedConditional { name: String, span: Span },
+    #[error("#{name} without #if")]
+    UnmatchedConditional { name: String, span: Span },
+    #[error("#{name} after #else")]
+    AfterElse { name: String, span: Span },
+    #[error("operator \"defined\" requires an identifier")]
+    InvalidDefined { span: Span },
+    #[error("#if with no expression")]
+    MissingExpression { span: Span },
+    #[error("token {found} is not valid in preprocessor expressions")]
+    InvalidExpression { found: String, span: Span },
+    #[error("missing binary operator before token {found}")]
+    MissingOperator { found: String, span: Span },
+    #[error("expected '{expected}' in preprocessor expression")]
+    ExpectedToken { expected: String, span: Span },
+    #[error("division by zero in #if")]
+    DivisionByZero { span: Span },
+    #[error("#error {message}")]
+    ErrorDirective { message: String, span: Span },
+    #[error("#line expects a line number and an optional \"FILENAME\"")]
+    MalformedLine { span: Span },
+    #[error("\"{found}\" after #line is not a positive integer")]
+    InvalidLineNumber { found: String, span: Span },
+    #[error("line number out of range")]
+    LineOutOfRange { span: Span },
+}
+
+impl PreprocessError {
+    pub fn span(&self) -> Span {
+        match self {
+            PreprocessError::Lex(e) => e.span(),
+            PreprocessError::UnknownDirective { span, .. }
+            | PreprocessError::MalformedInclude { span }
+            | PreprocessError::IncludeNotFound { span, .. }
+            | PreprocessError::Unreadable { span, .. }
+            | PreprocessError::IncludeCycle { span, .. }
+            | PreprocessError::MacroNameMissing { span }
+            | PreprocessError::InvalidMacroParams { span }
+            | PreprocessError::DuplicateMacroParam { span, .. }
+            | PreprocessError::StringifyNonParam { span }
+            | PreprocessError::PasteAtEdge { span }
+            | PreprocessError::MacroRedefined { span, .. }
+            | PreprocessError::InvalidPaste { span, .. }
+            | PreprocessError::UnterminatedInvocation { span, .. }
+            | PreprocessError::ArgCount { span, .. }
+            | PreprocessError::UnterminatedConditional { span, .. }
+            | PreprocessError::UnmatchedConditional { span, .. }
+            | PreprocessError::AfterElse { span, .. }
+            | PreprocessError::InvalidDefined { span }
+            | PreprocessError::MissingExpression { span }
+            | PreprocessError::InvalidExpression { span, .. }
+            | PreprocessError::MissingOperator { span, .. }
+            | PreprocessError::ExpectedToken { span, .. }
+            | PreprocessError::DivisionByZero { span }
+            | PreprocessError::ErrorDirective { span, .. }
+            | PreprocessError::MalformedLine { span }
+            | PreprocessError::InvalidLineNumber { span, .. }
+            | PreprocessError::LineOutOfRange { span } => *span,
+        }
+    }
+}
+
+/// Problems that are reported but don't stop preprocessing.
+#[derive(Error, Clone, PartialEq, Serialize, Deserialize, Debug)]
+pub enum PreprocessWarning {
+    #[error("#warning {message}")]
+    WarningDirective { message: String, span: Span },
+    #[error("ignoring #pragma {text}")]
+    UnknownPragma { text: String, span: Span },
+}
+
+impl PreprocessWarning {
+    pub fn span(&self) -> Span {
+        match self {
+            PreprocessWarning::WarningDirective { span, .. }
+            | PreprocessWarning::UnknownPragma { span, .. } => *span,
+        }
+    }
+}
+
+// the namespaces of the pragmas passed on to the parser. #pragma once is
+// handled by the preprocessor itself, and any other pragma is ignored
+const PRAGMAS: [&str; 4] = ["STDC", "GCC", "pack", "weak"];
+
+// a token on its way through macro expansion
+#[derive(Clone, Debug)]
+struct PpToken {
+    tok: Token,
+    space: bool,       // preceded by whitespace, which # keeps as one space
+    hide: Vec<Symbol>, // the macros that must not expand it again
+}
+
+impl PpToken {
+    fn is(&self, typ: TokenType) -> bool {
+        self.tok.typ == typ
+    }
+
+    // identifiers and keywords, which can both name macros
+    fn is_ident(&self) -> bool {
+        self.tok.symbol().is_some()
+    }
+}
+
+// macros whose expansion depends on where they are used
+#[derive(Copy, Clone, PartialEq, Debug)]
+enum Builtin {
+    File,
+    Line,
+}
+
+#[derive(Clone, Debug)]
+struct Macro {
+    params: Option<Vec<Symbol>>, // None for an object-like macro
+    body: Vec<PpToken>,
+    builtin: Option<Builtin>,
+}
+
+impl Macro {
+    fn param(&self, t: &PpToken) -> Option<usize> {
+        let params = self.params.as_ref()?;
+        let name = t.tok.symbol()?;
+        params.iter().position(|&p| p == name)
+    }
+
+    // C89 only allows a macro to be redefined with the same parameters and
+    // the same body, where any whitespace counts as the same
+    fn same(&self, other: &Macro, source_map: &SourceMap) -> bool {
+        self.builtin == other.builtin
+            && self.params == other.params
+            && self.body.len() == other.body.len()
+            && self
+                .body
+                .iter()
+                .zip(&other.body)
+                .enumerate()
+                .all(|(i, (a, b))| {
+                    a.tok.text(source_map) == b.tok.text(source_map)
+                        && (i == 0 || a.space == b.space)
+                })
+    }
+}
+
+// text the preprocessor makes up, like the result of # or ##, is spelled
+// in a scratch file of its own so that it reads back like any other token
+fn scratch(source_map: &mut SourceMap, text: String) -> Span {
+    let hi = text.len();
+    let file = source_map.add("<scratch space>", text);
+    Span { file, lo: 0, hi }
+}
+
+// the text of # applied to an argument
+fn stringize(source_map: &mut SourceMap, arg: &[PpToken], span: Span) -> PpToken {
+    let mut s = String::from('"');
+    for (i, t) in arg.iter().enumerate() {
+        if i > 0 && t.space {
+            s.push(' ');
+        }
+        let text = t.tok.text(source_map);
+        if t.is(TokenType::LiteralStr) || t.is(TokenType::LiteralChar) {
+            for c in text.chars() {
+                if c == '"' || c == '\\' {
+                    s.push('\\');
+                }
+                s.push(c);
+            }
+        } else {
+            s.push_str(&text);
+        }
+    }
+    s.push('"');
+
+    PpToken {
+        tok: Token {
+            lexeme: Lexeme::Spelled(scratch(source_map, s)),
+            typ: TokenType::LiteralStr,
+            span,
+        },
+        space: false,
+        hide: Vec::new(),
+    }
+}
+
+// a logical line of the source, without its newline. lexical errors are
+// kept in place so that they are only reported for lines that are used
+type Line = Vec<Result<Token, LexError>>;
+
+// the name and the rest of a directive line
+fn directive(line: &Line) -> Option<(Symbol, &[Result<Token, LexError>])> {
+    match line.as_slice() {
+        [Ok(hash), Ok(name), rest @ ..] if hash.typ == TokenType::Hash => {
+            Some((name.symbol()?, rest))
+        }
+        _ => None,
+    }
+}
+
+// the macro of an include guard: a file wrapped in `#ifndef G`, `#define G`
+// and a matching `#endif`, which has no effect when it is included again.
+// like gcc, a file whose #ifndef has an #else or #elif isn't guarded, since
+// including it again keeps that group
+fn guard(lines: &[Line]) -> Option<Symbol> {
+    if lines.len() < 3 {
+        return None;
+    }
+
+    let g = match directive(&lines[0])? {
+        (ifndef, [Ok(g)]) if ifndef == "ifndef" => g.symbol()?,
+        _ => return None,
+    };
+    match directive(&lines[1])? {
+        (define, [Ok(name), ..]) if define == "define" && name.symbol() == Some(g) => (),
+        _ => return None,
+    }
+
+    // the #endif closing the #ifndef must be the last line
+    let mut depth = 0;
+    for (i, line) in lines.iter().enumerate() {
+        match directive(line) {
+            Some((name, _)) if name == "if" || name == "ifdef" || name == "ifndef" => depth += 1,
+            Some((name, _)) if (name == "else" || name == "elif") && depth == 1 => return None,
+            Some((name, _)) if name == "endif" => {
+                depth -= 1;
+                if depth == 0 {
+                    return (i == lines.len() - 1).then_some(g);
+                }
+            }
+            _ => (),
+        }
+    }
+
+    None
+}
+
+// an #if group and the #elif and #else groups that follow it
+struct Conditional {
+    name: Symbol, // of the directive that opened the group
+    span: Span,
+    outer: bool,  // the enclosing group is kept
+    active: bool, // this group is kept
+    taken: bool,  // some group of the chain has been kept
+    seen_else: bool,
+}
+
+// #if arithmetic is done in 64 bits like gcc does, whatever the target's
+// long, with C's usual conversions between signed and unsigned
+#[derive(Copy, Clone, PartialEq, Debug)]
+struct Value {
+    n: i64,
+    unsigned: bool,
+}
+
+impl Value {
+    fn int(n: i64) -> Self {
+        Value { n, unsigned: false }
+    }
+
+    fn truth(b: bool) -> Self {
+        Value::int(b as i64)
+    }
+}
+
+fn precedence(typ: TokenType) -> Option<u8> {
+    match typ {
+        TokenType::BarBar => Some(1),
+        TokenType::AmpAmp => Some(2),
+        TokenType::Bar => Some(3),
+        TokenType::Caret => Some(4),
+        TokenType::Amp => Some(5),
+        TokenType::EqualsEquals | TokenType::BangEquals => Some(6),
+        TokenType::LeftAngleBracket
+        | TokenType::RightAngleBracket
+        | TokenType::LeftAngleBracketEquals
+        | TokenType::RightAngleBracketEquals => Some(7),
+        TokenType::DoubleLeftAngleBracket | TokenType::DoubleRightAngleBracket => Some(8),
+        TokenType::Plus | TokenType::Minus => Some(9),
+        TokenType::Star | TokenType::Slash | TokenType::Percent => Some(10),
+        _ => None,
+    }
+}
+
+// evaluates an #if expression whose macros have been expanded. operands
+// that are not evaluated, like the right of a false &&, can't fail
+struct Eval<'t> {
+    source_map: &'t SourceMap,
+    tokens: &'t [PpToken],
+    pos: usize,
+    span: Span, // of the directive, for errors at the end of the line
+}
+
+impl Eval<'_> {
+    fn peek(&self) -> Option<&PpToken> {
+        self.tokens.get(self.pos)
+    }
+
+    fn expect(&mut self, typ: TokenType, expected: &str) -> Result<(), PreprocessError> {
+        match self.peek() {
+            Some(t) if t.is(typ) => {
+                self.pos += 1;
+                Ok(())
+            }
+            t => Err(PreprocessError::ExpectedToken {
+                expected: expected.to_owned(),
+                span: t.map_or(self.span, |t| t.tok.span),
+            }),
+        }
+    }
+
+    fn eval(mut self) -> Result<bool, PreprocessError> {
+        if self.tokens.is_empty() {
+            return Err(PreprocessError::MissingExpression { span: self.span });
+        }
+
+        let v = self.ternary(true)?;
+        match self.peek() {
+            Some(t) => Err(PreprocessError::MissingOperator {
+                found: t.tok.text(self.source_map).into_owned(),
+                span: t.tok.span,
+            }),
+            None => Ok(v.n != 0),
+        }
+    }
+
+    fn ternary(&mut self, eval: bool) -> Result<Value, PreprocessError> {
+        let cond = self.binary(1, eval)?;
+        if !self.peek().is_some_and(|t| t.is(TokenType::Question)) {
+            return Ok(cond);
+        }
+        self.pos += 1;
+
+        let then = self.ternary(eval && cond.n != 0)?;
+        self.expect(TokenType::PuncColon, ":")?;
+        let els = self.ternary(eval && cond.n == 0)?;
+
+        let n = if cond.n != 0 { then.n } else { els.n };
+        Ok(Value {
+            n,
+            unsigned: then.unsigned || els.unsigned,
+        })
+    }
+
+    fn binary(&mut self, min: u8, eval: bool) -> Result<Value, PreprocessError> {
+        let mut l = self.unary(eval)?;
+
+        while let Some(op) = self.peek().map(|t| t.tok) {
+            let Some(prec) = precedence(op.typ).filter(|&p| p >= min) else {
+                break;
+            };
+            self.pos += 1;
+
+            l = match op.typ {
+                TokenType::AmpAmp => {
+                    let r = self.binary(prec + 1, eval && l.n != 0)?;
+                    Value::truth(l.n != 0 && r.n != 0)
+                }
+                TokenType::BarBar => {
+                    let r = self.binary(prec + 1, eval && l.n == 0)?;
+                    Value::truth(l.n != 0 || r.n != 0)
+                }
+                typ => {
+                    let r = self.binary(prec + 1, eval)?;
+                    operate(typ, l, r, op.span, eval)?
+                }
+            };
+        }
+
+        Ok(l)
+    }
+
+    fn unary(&mut self, eval: bool) -> Result<Value, PreprocessError> {
+        let Some(t) = self.peek().cloned() else {
+            return Err(PreprocessError::MissingExpression { span: self.span });
+        };
+        self.pos += 1;
+
+        match t.tok.typ {
+            TokenType::LiteralInt => {
+                let (n, unsigned) = lexer::pp_int_literal(&t.tok.text(self.source_map), t.tok.span)
+                    .map_err(PreprocessError::Lex)?;
+                Ok(Value {
+                    n: n as i64,
+                    unsigned,
+                })
+            }
+            TokenType::LiteralChar => {
+                let c = lexer::char_literal(&t.tok.text(self.source_map), t.tok.span)
+                    .map_err(PreprocessError::Lex)?;
+                Ok(Value::int(c as i64))
+            }
+            TokenType::PuncLeftParen => {
+                let v = self.ternary(eval)?;
+                self.expect(TokenType::PuncRightParen, ")")?;
+                Ok(v)
+            }
+            TokenType::Plus => self.unary(eval),
+            TokenType::Minus => {
+                let v = self.unary(eval)?;
+                Ok(Value {
+                    n: v.n.wrapping_neg(),
+                    ..v
+                })
+            }
+            TokenType::Tilde => {
+                let v = self.unary(eval)?;
+                Ok(Value { n: !v.n, ..v })
+            }
+            TokenType::Bang => {
+                let v = self.unary(eval)?;
+                Ok(Value::truth(v.n == 0))
+            }
+            // identifiers left after expansion aren't macros
+            _ if t.is_ident() => Ok(Value::int(0)),
+            _ => Err(PreprocessError::InvalidExpression {
+                found: t.tok.text(self.source_map).into_owned(),
+                span: t.tok.span,
+            }),
+        }
+    }
+}
+
+fn operate(
+    op: TokenType,
+    l: Value,
+    r: Value,
+    span: Span,
+    eval: bool,
+) -> Result<Value, PreprocessError> {
+    let unsigned = l.unsigned || r.unsigned;
+    let (a, b) = (l.n, r.n);
+    let (ua, ub) = (a as u64, b as u64);
+
+    let n = match op {
+        TokenType::Star => a.wrapping_mul(b),
+        TokenType::Slash | TokenType::Percent if b == 0 => {
+            if eval {
+                return Err(PreprocessError::DivisionByZero { span });
+            }
+            0
+        }
+        TokenType::Slash if unsigned => (ua / ub) as i64,
+        TokenType::Slash => a.wrapping_div(b),
+        TokenType::Percent if unsigned => (ua % ub) as i64,
+        TokenType::Percent => a.wrapping_rem(b),
+        TokenType::Plus => a.wrapping_add(b),
+        TokenType::Minus => a.wrapping_sub(b),
+
+        // a shift has the type of its left operand
+        TokenType::DoubleLeftAngleBracket => {
+            return Ok(Value {
+                n: a.wrapping_shl(b as u32),
+                ..l
+            })
+        }
+        TokenType::DoubleRightAngleBracket if l.unsigned => {
+            return Ok(Value {
+                n: ua.wrapping_shr(b as u32) as i64,
+                ..l
+            })
+        }
+        TokenType::DoubleRightAngleBracket => {
+            return Ok(Value {
+                n: a.wrapping_shr(b as u32),
+                ..l
+            })
+        }
+
+        TokenType::LeftAngleBracket => {
+            return Ok(Value::truth(if unsigned { ua < ub } else { a < b }))
+        }
+        TokenType::RightAngleBracket => {
+            return Ok(Value::truth(if unsigned { ua > ub } else { a > b }))
+        }
+        TokenType::LeftAngleBracketEquals => {
+            return Ok(Value::truth(if unsigned { ua <= ub } else { a <= b }))
+        }
+        TokenType::RightAngleBracketEquals => {
+            return Ok(Value::truth(if unsigned { ua >= ub } else { a >= b }))
+        }
+        TokenType::EqualsEquals => return Ok(Value::truth(a == b)),
+        TokenType::BangEquals => return Ok(Value::truth(a != b)),
+
+        TokenType::Amp => a & b,
+        TokenType::Caret => a ^ b,
+        TokenType::Bar => a | b,
+        _ => unreachable!("{op:?} is not a binary operator"),
+    };
+
+    Ok(Value { n, unsigned })
+}
+
+// __DATE__ and __TIME__ of a time in seconds since the epoch, in UTC
+fn date_time(secs: u64) -> (String, String) {
+    const MONTHS: [&str; 12] = [
+        "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+    ];
+
+    // days to a civil date, from Howard Hinnant's chrono-compatible
+    // low-level date algorithms
+    let days = (secs / 86400) as i64 + 719468;
+    let era = days / 146097;
+    let doe = days - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + (month <= 2) as i64;
+
+    let secs = secs % 86400;
+    (
+        format!("{} {day:>2} {year}", MONTHS[month as usize - 1]),
+        format!("{:02}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60),
+    )
+}
+
+// the text of tokens, with any whitespace between them as one space, like
+// a <...> header name that the lexer has split up
+fn spelling(source_map: &SourceMap, tokens: &[PpToken]) -> String {
+    let mut name = String::new();
+    for (i, t) in tokens.iter().enumerate() {
+        if i > 0 && t.space {
+            name.push(' ');
+        }
+        name.push_str(&t.tok.text(source_map));
+    }
+
+    name
+}
+
+/// Translation phases 3 and 4: runs directives over the tokens of a file and
+/// the files it includes, producing the tokens the parser sees.
+pub struct Preprocessor<'a> {
+    source_map: &'a mut SourceMap,
+    std: Std,
+    include_dirs: Vec<PathBuf>,
+    system_dirs: Vec<PathBuf>,
+    includes: Vec<PathBuf>, // the files being preprocessed, innermost last
+    once: HashSet<PathBuf>,
+    guards: HashMap<PathBuf, Symbol>,
+    macros: HashMap<Symbol, Macro>,
+    target: Target,
+    command_line: String, // -D and -U, as directives
+    timestamp: Option<u64>,
+    tokens: Vec<(Token, bool)>,    // and whether whitespace came before it
+    markers: Vec<(usize, Marker)>, // before which token
+    errors: Vec<PreprocessError>,
+    warnings: Option<&'a mut Vec<PreprocessWarning>>,
+    stopped: bool, // by #error
+}
+
+impl<'a> Preprocessor<'a> {
+    pub fn new(source_map: &'a mut SourceMap) -> Self {
+        Preprocessor {
+            source_map,
+            std: Std::default(),
+            include_dirs: Vec::new(),
+            system_dirs: Vec::new(),
+            includes: Vec::new(),
+            once: HashSet::new(),
+            guards: HashMap::new(),
+            macros: HashMap::new(),
+            target: Target::default(),
+            command_line: String::new(),
+            timestamp: None,
+            tokens: Vec::new(),
+            markers: Vec::new(),
+            errors: Vec::new(),
+            warnings: None,
+            stopped: false,
+        }
+    }
+
+    pub fn std(mut self, std: Std) -> Self {
+        self.std = std;
+        self
+    }
+
+    /// A directory searched by both `#include "..."` and `#include <...>`,
+    /// like gcc's -I.
+    pub fn include_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.include_dirs.push(dir.into());
+        self
+    }
+
+    /// A directory searched after every include directory.
+    pub fn system_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.system_dirs.push(dir.into());
+        self
+    }
+
+    /// The target whose macros, like `__riscv_xlen`, are predefined.
+    pub fn target(mut self, target: Target) -> Self {
+        self.target = target;
+        self
+    }
+
+    /// Defines a macro like gcc's -D: `NAME` defines it as 1, and
+    /// `NAME=value` or `NAME(params)=body` as the text after the =.
+    pub fn define(mut self, def: &str) -> Self {
+        let line = match def.split_once('=') {
+            Some((name, value)) => format!("#define {name} {value}\n"),
+            None => format!("#define {def} 1\n"),
+        };
+        self.command_line.push_str(&line);
+        self
+    }
+
+    /// Undefines a macro like gcc's -U, after the -D options before it.
+    pub fn undef(mut self, name: &str) -> Self {
+        self.command_line.push_str(&format!("#undef {name}\n"));
+        self
+    }
+
+    /// The time, in seconds since the epoch, of `__DATE__` and `__TIME__`.
+    /// It defaults to now, and can be pinned for reproducible builds.
+    pub fn timestamp(mut self, secs: u64) -> Self {
+        self.timestamp = Some(secs);
+        self
+    }
+
+    /// Where warnings go. Without somewhere to go they are dropped.
+    pub fn warnings(mut self, warnings: &'a mut Vec<PreprocessWarning>) -> Self {
+        self.warnings = Some(warnings);
+        self
+    }
+
+    /// Preprocesses a file, reporting every error rather than stopping at
+    /// the first one. The constants and literals it expands to are checked
+    /// here, as they become tokens, so those only skipped or stringized
+    /// can be malformed.
+    pub fn preprocess(mut self, file: FileId) -> Result<Vec<Token>, Vec<PreprocessError>> {
+        self.run(file)?;
+
+        let tokens = self.tokens.into_iter().map(|(t, _)| t).collect::<Vec<_>>();
+        let errors = tokens
+            .iter()
+            .filter_map(|t| t.check(self.source_map).err())
+            .map(PreprocessError::Lex)
+            .collect::<Vec<_>>();
+        if errors.is_empty() {
+            Ok(tokens)
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// Preprocesses a file into text, like gcc -E: tokens are printed on the
+    /// lines they came from, with `# <line> "<file>"` markers wherever the
+    /// output moves to another file or skips more than a few lines. A
+    /// marker entering an #include is flagged 1, and one returning from it
+    /// is flagged 2.
+    pub fn preprocess_to_text(mut self, file: FileId) -> Result<String, Vec<PreprocessError>> {
+        self.run(file)?;
+
+        let name = self.source_map.file(file).name().to_owned();
+        let mut printer = Printer {
+            source_map: self.source_map,
+            out: String::new(),
+            name: name.clone(),
+            line: 1,
+        };
+        printer.marker(&name, 1, "");
+
+        let mut markers = self.markers.into_iter().peekable();
+        let mut prev: Option<Token> = None;
+        for (i, (t, space)) in self.tokens.into_iter().enumerate() {
+            while let Some((_, marker)) = markers.next_if(|(at, _)| *at == i) {
+                printer.follow(marker);
+            }
+
+            let location = self.source_map.location(t.span);
+            if printer.goto(&location.name, location.line) {
+                printer.out.push_str(&" ".repeat(location.col - 1));
+            } else if space || prev.is_some_and(|p| would_paste(self.source_map, p, t)) {
+                printer.out.push(' ');
+            }
+            if t.typ == TokenType::Pragma {
+                printer.out.push_str("#pragma ");
+            }
+            printer.out.push_str(&t.text(self.source_map));
+            prev = Some(t);
+        }
+        for (_, marker) in markers {
+            printer.follow(marker);
+        }
+        if !printer.out.ends_with('\n') {
+            printer.out.push('\n');
+        }
+
+        Ok(printer.out)
+    }
+
+    fn run(&mut self, file: FileId) -> Result<(), Vec<PreprocessError>> {
+        self.predefine();
+
+        let name = self.source_map.file(file).name();
+        let path = fs::canonicalize(name).unwrap_or_else(|_| PathBuf::from(name));
+        self.file(file, path);
+
+        if self.errors.is_empty() {
+            Ok(())
+        } else {
+            Err(std::mem::take(&mut self.errors))
+        }
+    }
+
+    // the predefined macros and those of the command line are defined by
+    // preprocessing their directives as if they were a file, which gcc
+    // calls <command-line>
+    fn predefine(&mut self) {
+        for (name, builtin) in [("__FILE__", Builtin::File), ("__LINE__", Builtin::Line)] {
+            let m = Macro {
+                params: None,
+                body: Vec::new(),
+                builtin: Some(builtin),
+            };
+            self.macros.insert(Symbol::intern(name), m);
+        }
+
+        let secs = self.timestamp.unwrap_or_else(|| {
+            let now = SystemTime::now().duration_since(UNIX_EPOCH);
+            now.map_or(0, |d| d.as_secs())
+        });
+        let (date, time) = date_time(secs);
+
+        let mut predefs = vec![
+            ("__STDC__", "1".to_owned()),
+            ("__DATE__", format!("\"{date}\"")),
+            ("__TIME__", format!("\"{time}\"")),
+        ];
+        if self.std >= Std::C99 {
+            predefs.push(("__STDC_VERSION__", "199901L".to_owned()));
+        }
+        predefs.extend(self.target.macros());
+
+        let mut src = predefs
+            .iter()
+            .map(|(name, value)| format!("#define {name} {value}\n"))
+            .collect::<String>();
+        src.push_str(&self.command_line);
+
+        let file = self.source_map.add("<command-line>", src);
+        self.file(file, PathBuf::from("<command-line>"));
+    }
+
+    fn lines(&self, file: FileId) -> Vec<Line> {
+        let lexer = Lexer::new(self.source_map.file(file).src(), file)
+            .std(self.std)
+            .keep_newlines(true)
+            .pp_tokens(true);
+
+        let mut lines = vec![Line::new()];
+        for t in lexer {
+            match t {
+                Ok(t) if t.typ == TokenType::Newline => lines.push(Line::new()),
+                t => lines.last_mut().unwrap().push(t),
+            }
+        }
+        lines.retain(|line| !line.is_empty());
+
+        lines
+    }
+
+    fn file(&mut self, file: FileId, path: PathBuf) {
+        let lines = self.lines(file);
+        if let Some(g) = guard(&lines) {
+            self.guards.insert(path.clone(), g);
+        }
+
+        // text is expanded a run of lines at a time, since the arguments of
+        // a function-like macro can span lines. lines in groups that are
+        // skipped are never looked at past their directive name, so they
+        // don't have to lex
+        self.includes.push(path);
+        let mut conds = Vec::new();
+        let mut text = Vec::new();
+        for line in &lines {
+            if self.stopped {
+                self.includes.pop();
+                return;
+            }
+
+            let active = conds.last().is_none_or(|c: &Conditional| c.active);
+            let conditional = directive(line).is_some_and(|(name, _)| {
+                matches!(
+                    name.as_str(),
+                    "if" | "ifdef" | "ifndef" | "elif" | "else" | "endif"
+                )
+            });
+
+            match line.first() {
+                Some(Ok(hash)) if hash.typ == TokenType::Hash && conditional => {
+                    self.flush(&mut text);
+                    self.conditional(*hash, line, &mut conds);
+                }
+                _ if !active => (),
+                Some(Ok(hash)) if hash.typ == TokenType::Hash => {
+                    self.flush(&mut text);
+                    match directive(line) {
+                        Some((name, _)) if name == "error" || name == "warning" => {
+                            self.diagnostic(line)
+                        }
+                        _ => {
+                            let tokens = self.pp_tokens(&line[1..]);
+                            self.directive(*hash, &tokens);
+                        }
+                    }
+                }
+                _ => text.extend(self.pp_tokens(line)),
+            }
+        }
+        self.flush(&mut text);
+        self.includes.pop();
+
+        for c in conds {
+            self.errors.push(PreprocessError::UnterminatedConditional {
+                name: c.name.to_string(),
+                span: c.span,
+            });
+        }
+    }
+
+    fn pp_tokens(&mut self, line: &[Result<Token, LexError>]) -> Vec<PpToken> {
+        let mut tokens = Vec::with_capacity(line.len());
+        let mut prev: Option<Token> = None;
+        for t in line {
+            match t {
+                Ok(t) => {
+                    tokens.push(PpToken {
+                        tok: *t,
+                        space: prev.is_none_or(|p| p.span.hi != t.span.lo),
+                        hide: Vec::new(),
+                    });
+                    prev = Some(*t);
+                }
+                Err(e) => self.errors.push(e.clone().into()),
+            }
+        }
+
+        tokens
+    }
+
+    fn flush(&mut self, text: &mut Vec<PpToken>) {
+        let expanded = self.expand(std::mem::take(text));
+        self.tokens
+            .extend(expanded.into_iter().map(|t| (t.tok, t.space)));
+    }
+
+    fn warn(&mut self, warning: PreprocessWarning) {
+        if let Some(warnings) = &mut self.warnings {
+            warnings.push(warning);
+        }
+    }
+
+    // #error and #warning, whose message is the rest of the line as written
+    // after translation phases 1 and 2: it doesn't have to be made of
+    // tokens, so it can have an apostrophe
+    fn diagnostic(&mut self, line: &Line) {
+        let [Ok(hash), Ok(name), ..] = &line[..] else {
+            unreachable!("not a directive");
+        };
+        let end = match line.last().unwrap() {
+            Ok(t) => t.span.hi,
+            Err(e) => e.span().hi,
+        };
+        let rest = Span {
+            lo: name.span.hi,
+            hi: end,
+            ..name.span
+        };
+        let message = splice(self.source_map.snippet(rest))
+            .text()
+            .trim()
+            .to_owned();
+        let span = Span {
+            hi: name.span.hi,
+            ..hash.span
+        };
+
+        if name.symbol().is_some_and(|name| name == "error") {
+            self.errors
+                .push(PreprocessError::ErrorDirective { message, span });
+            self.stopped = true;
+        } else {
+            self.warn(PreprocessWarning::WarningDirective { message, span });
+        }
+    }
+
+    fn directive(&mut self, hash: Token, tokens: &[PpToken]) {
+        let Some((name, args)) = tokens.split_first() else {
+            return; // the null directive
+        };
+        match name.tok.symbol().map(Symbol::as_str) {
+            Some("include") => self.include(hash, args),
+            Some("define") => self.define_macro(hash, args),
+            Some("undef") => match args.first().and_then(|name| name.tok.symbol()) {
+                Some(name) => {
+                    self.macros.remove(&name);
+                }
+                None => self
+                    .errors
+                    .push(PreprocessError::MacroNameMissing { span: hash.span }),
+            },
+            Some("line") => self.line(hash, args),
+            Some("pragma") => self.pragma(hash, args),
+            _ => self.errors.push(PreprocessError::UnknownDirective {
+                name: name.tok.text(self.source_map).into_owned(),
+                span: name.tok.span,
+            }),
+        }
+    }
+
+    fn conditional(&mut self, hash: Token, line: &Line, conds: &mut Vec<Conditional>) {
+        let (name, rest) = directive(line).unwrap();
+        let span = Span {
+            hi: line[1].as_ref().unwrap().span.hi,
+            ..hash.span
+        };
+        let outer = conds.last().is_none_or(|c| c.active);
+
+        match (name.as_str(), conds.last_mut()) {
+            ("if" | "ifdef" | "ifndef", _) => {
+                let active = outer && self.condition(name, span, rest);
+                conds.push(Conditional {
+                    name,
+                    span,
+                    outer,
+                    active,
+                    taken: active,
+                    seen_else: false,
+                });
+            }
+            (_, None) => self.errors.push(PreprocessError::UnmatchedConditional {
+                name: name.to_string(),
+                span,
+            }),
+            ("endif", Some(_)) => {
+                conds.pop();
+            }
+            ("elif" | "else", Some(c)) if c.seen_else => {
+                self.errors.push(PreprocessError::AfterElse {
+                    name: name.to_string(),
+                    span,
+                });
+            }
+            ("elif", Some(c)) => {
+                c.active = c.outer && !c.taken && self.condition(name, span, rest);
+                c.taken |= c.active;
+            }
+            (_, Some(c)) => {
+                c.active = c.outer && !c.taken;
+                c.taken = true;
+                c.seen_else = true;
+            }
+        }
+    }
+
+    // whether the group of an #if, #ifdef, #ifndef or #elif is kept
+    fn condition(&mut self, name: Symbol, span: Span, rest: &[Result<Token, LexError>]) -> bool {
+        let args = self.pp_tokens(rest);
+        // a line that doesn't lex has just been reported, and would only be
+        // reported again as missing what didn't lex
+        if rest.iter().any(Result::is_err) {
+            return false;
+        }
+
+        if name == "ifdef" || name == "ifndef" {
+            return match args.first().and_then(|m| m.tok.symbol()) {
+                Some(m) => self.macros.contains_key(&m) == (name == "ifdef"),
+                None => {
+                    self.errors.push(PreprocessError::MacroNameMissing { span });
+                    false
+                }
+            };
+        }
+
+        // defined is evaluated before expansion, so that it sees the names
+        // of macros rather than what they expand to
+        let mut tokens = Vec::with_capacity(args.len());
+        let mut i = 0;
+        while i < args.len() {
+            if args[i].tok.symbol().is_none_or(|s| s != "defined") {
+                tokens.push(args[i].clone());
+                i += 1;
+                continue;
+            }
+
+            let (m, len) = match &args[i + 1..] {
+                [m, ..] if m.is_ident() => (m, 2),
+                [l, m, r, ..]
+                    if l.is(TokenType::PuncLeftParen)
+                        && m.is_ident()
+                        && r.is(TokenType::PuncRightParen) =>
+                {
+                    (m, 4)
+                }
+                _ => {
+                    self.errors.push(PreprocessError::InvalidDefined {
+                        span: args[i].tok.span,
+                    });
+                    return false;
+                }
+            };
+            let defined = m.tok.symbol().is_some_and(|m| self.macros.contains_key(&m));
+            let one_or_zero = scratch(self.source_map, (defined as u8).to_string());
+            tokens.push(PpToken {
+                tok: Token {
+                    lexeme: Lexeme::Spelled(one_or_zero),
+                    typ: TokenType::LiteralInt,
+                    span: args[i].tok.span,
+                },
+                ..args[i].clone()
+            });
+            i += len;
+        }
+
+        let tokens = self.expand(tokens);
+        let eval = Eval {
+            source_map: self.source_map,
+            tokens: &tokens,
+            pos: 0,
+            span,
+        };
+        eval.eval().unwrap_or_else(|e| {
+            self.errors.push(e);
+            false
+        })
+    }
+
+    fn include(&mut self, hash: Token, args: &[PpToken]) {
+        // #include MACRO names the file by what MACRO expands to
+        let expanded;
+        let args = match args.first() {
+            Some(t) if t.is_ident() => {
+                expanded = self.expand(args.to_vec());
+                &expanded[..]
+            }
+            _ => args,
+        };
+
+        let (name, quoted, span) = match args {
+            [t] if t.is(TokenType::LiteralStr) => {
+                let lexeme = t.tok.text(self.source_map);
+                (lexeme[1..lexeme.len() - 1].to_owned(), true, t.tok.span)
+            }
+            [open, name @ .., close]
+                if open.is(TokenType::LeftAngleBracket)
+                    && close.is(TokenType::RightAngleBracket) =>
+            {
+                let span = Span {
+                    hi: close.tok.span.hi,
+                    ..open.tok.span
+                };
+                (spelling(self.source_map, name), false, span)
+            }
+            _ => {
+                self.errors
+                    .push(PreprocessError::MalformedInclude { span: hash.span });
+                return;
+            }
+        };
+
+        // "..." is looked up next to the including file first
+        let current = Path::new(self.source_map.file(hash.span.file).name());
+        let here = quoted.then(|| current.parent().unwrap_or(Path::new("")).to_owned());
+        let found = here
+            .iter()
+            .chain(&self.include_dirs)
+            .chain(&self.system_dirs)
+            .map(|dir| dir.join(&name))
+            .find(|path| path.is_file());
+        let Some(found) = found else {
+            self.errors
+                .push(PreprocessError::IncludeNotFound { name, span });
+            return;
+        };
+
+        let path = fs::canonicalize(&found).unwrap_or_else(|_| found.clone());
+        let guarded = self
+            .guards
+            .get(&path)
+            .is_some_and(|g| self.macros.contains_key(g));
+        if guarded || self.once.contains(&path) {
+            return;
+        }
+        if self.includes.contains(&path) {
+            self.errors
+                .push(PreprocessError::IncludeCycle { name, span });
+            return;
+        }
+
+        match self.source_map.load(&found) {
+            Ok(file) => {
+                self.markers.push((self.tokens.len(), Marker::Enter(file)));
+                self.file(file, path);
+
+                let location = self.source_map.location(hash.span);
+                let marker = Marker::Return(location.name, location.line + 1);
+                self.markers.push((self.tokens.len(), marker));
+            }
+            Err(e) => self.errors.push(PreprocessError::Unreadable {
+                name,
+                reason: e.to_string(),
+                span,
+            }),
+        }
+    }
+
+    fn line(&mut self, hash: Token, args: &[PpToken]) {
+        // the lines renumbered are those after the directive, which may
+        // itself span lines
+        let end = args.last().map_or(hash.span.hi, |t| t.tok.span.hi);
+
+        let args = self.expand(args.to_vec());
+        let (number, name) = match &args[..] {
+            [number] => (number, None),
+            [number, name] if name.is(TokenType::LiteralStr) => (number, Some(name)),
+            _ => {
+                self.errors
+                    .push(PreprocessError::MalformedLine { span: hash.span });
+                return;
+            }
+        };
+
+        let digits = number.tok.text(self.source_map);
+        if !digits.bytes().all(|b| b.is_ascii_digit()) || digits.bytes().all(|b| b == b'0') {
+            self.errors.push(PreprocessError::InvalidLineNumber {
+                found: digits.into_owned(),
+                span: number.tok.span,
+            });
+            return;
+        }
+        let max = if self.std >= Std::C99 {
+            2147483647
+        } else {
+            32767
+        };
+        let Some(line) = digits.parse().ok().filter(|&n| n <= max) else {
+            self.errors.push(PreprocessError::LineOutOfRange {
+                span: number.tok.span,
+            });
+            return;
+        };
+
+        // the name is a string literal, and __FILE__ escapes it again
+        let name = name.map(|t| {
+            let lexeme = t.tok.text(self.source_map);
+            let mut name = String::new();
+            let mut chars = lexeme[1..lexeme.len() - 1].chars();
+            while let Some(c) = chars.next() {
+                name.push(if c == '\\' {
+                    chars.next().unwrap_or(c)
+                } else {
+                    c
+                });
+            }
+            name
+        });
+
+        let presumed = name
+            .clone()
+            .unwrap_or_else(|| self.source_map.location(hash.span).name);
+        self.markers
+            .push((self.tokens.len(), Marker::Line(presumed, line)));
+        self.source_map.remap_lines(hash.span.file, end, line, name);
+    }
+
+    fn pragma(&mut self, hash: Token, args: &[PpToken]) {
+        let Some(first) = args.first() else {
+            return;
+        };
+        let span = Span {
+            hi: args.last().unwrap().tok.span.hi,
+            ..hash.span
+        };
+        let text = spelling(self.source_map, args);
+
+        if text == "once" {
+            let path = self.includes.last().unwrap().clone();
+            self.once.insert(path);
+        } else if first
+            .tok
+            .symbol()
+            .is_some_and(|s| PRAGMAS.contains(&s.as_str()))
+        {
+            let pragma = Token {
+                lexeme: Lexeme::Spelled(scratch(self.source_map, text)),
+                typ: TokenType::Pragma,
+                span,
+            };
+            self.tokens.push((pragma, false));
+        } else {
+            self.warn(PreprocessWarning::UnknownPragma { text, span });
+        }
+    }
+
+    fn define_macro(&mut self, hash: Token, args: &[PpToken]) {
+        let Some((name, symbol, rest)) = args
+            .split_first()
+            .and_then(|(name, rest)| Some((name, name.tok.symbol()?, rest)))
+        else {
+            self.errors
+                .push(PreprocessError::MacroNameMissing { span: hash.span });
+            return;
+        };
+
+        // a function-like macro has its ( right after the name
+        let (params, body) = match rest.first() {
+            Some(lparen) if lparen.is(TokenType::PuncLeftParen) && !lparen.space => {
+                let Some(close) = rest.iter().position(|t| t.is(TokenType::PuncRightParen)) else {
+                    self.errors.push(PreprocessError::InvalidMacroParams {
+                        span: lparen.tok.span,
+                    });
+                    return;
+                };
+
+                let mut params = Vec::new();
+                let list = &rest[1..close];
+                for (i, t) in list.iter().enumerate() {
+                    // names separated by commas, without a trailing one
+                    let valid = if i % 2 == 0 {
+                        t.is_ident()
+                    } else {
+                        t.is(TokenType::PuncComma) && i != list.len() - 1
+                    };
+                    if !valid {
+                        self.errors
+                            .push(PreprocessError::InvalidMacroParams { span: t.tok.span });
+                        return;
+                    }
+                    if let Some(param) = t.tok.symbol() {
+                        if params.contains(&param) {
+                            self.errors.push(PreprocessError::DuplicateMacroParam {
+                                name: param.to_string(),
+                                span: t.tok.span,
+                            });
+                            return;
+                        }
+                        params.push(param);
+                    }
+                }
+
+                (Some(params), &rest[close + 1..])
+            }
+            _ => (None, rest),
+        };
+        let m = Macro {
+            params,
+            body: body.to_vec(),
+            builtin: None,
+        };
+
+        for (i, t) in m.body.iter().enumerate() {
+            let stringized = m.body.get(i + 1).and_then(|p| m.param(p));
+            if t.is(TokenType::Hash) && m.params.is_some() && stringized.is_none() {
+                self.errors
+                    .push(PreprocessError::StringifyNonParam { span: t.tok.span });
+                return;
+            }
+            if t.is(TokenType::HashHash) && (i == 0 || i == m.body.len() - 1) {
+                self.errors
+                    .push(PreprocessError::PasteAtEdge { span: t.tok.span });
+                return;
+            }
+        }
+
+        if self
+            .macros
+            .get(&symbol)
+            .is_some_and(|old| !old.same(&m, self.source_map))
+        {
+            self.errors.push(PreprocessError::MacroRedefined {
+                name: symbol.to_string(),
+                span: name.tok.span,
+            });
+            return;
+        }
+        self.macros.insert(symbol, m);
+    }
+
+    // expands the macros in ts, rescanning each expansion along with the
+    // rest of the tokens. a token is never expanded by a macro in its hide
+    // set, which holds the macros whose expansion produced it: this is what
+    // stops recursion
+    fn expand(&mut self, ts: Vec<PpToken>) -> Vec<PpToken> {
+        let mut input = ts.into_iter().rev().collect::<Vec<_>>(); // next token last
+        let mut output = Vec::new();
+
+        while let Some(t) = input.pop() {
+            let Some(name) = t.tok.symbol() else {
+                output.push(t);
+                continue;
+            };
+            let m = match self.macros.get(&name) {
+                Some(m) if !t.hide.contains(&name) => m.clone(),
+                _ => {
+                    output.push(t);
+                    continue;
+                }
+            };
+            if let Some(builtin) = m.builtin {
+                output.push(self.builtin(builtin, t));
+                continue;
+            }
+
+            let (args, mut hide) = match &m.params {
+                None => (Vec::new(), t.hide.clone()),
+                // without arguments, the name of a function-like macro is
+                // just an identifier
+                Some(_) if !input.last().is_some_and(|t| t.is(TokenType::PuncLeftParen)) => {
+                    output.push(t);
+                    continue;
+                }
+                Some(params) => {
+                    input.pop();
+                    let Some((args, rparen)) = self.args(&mut input) else {
+                        self.errors.push(PreprocessError::UnterminatedInvocation {
+                            name: name.to_string(),
+                            span: t.tok.span,
+                        });
+                        break;
+                    };
+
+                    // f() passes one empty argument, which is no arguments
+                    // to a macro without parameters
+                    let got = if params.is_empty() && args.len() == 1 && args[0].is_empty() {
+                        0
+                    } else {
+                        args.len()
+                    };
+                    if got != params.len() {
+                        self.errors.push(PreprocessError::ArgCount {
+                            name: name.to_string(),
+                            expected: params.len(),
+                            got,
+                            span: t.tok.span,
+                        });
+                        continue;
+                    }
+
+                    let hide = t
+                        .hide
+                        .iter()
+                        .filter(|h| rparen.hide.contains(h))
+                        .copied()
+                        .collect();
+                    (args, hide)
+                }
+            };
+            hide.push(name);
+
+            let mut expansion = self.subst(&m, &args, t.tok.span);
+            for e in &mut expansion {
+                for h in &hide {
+                    if !e.hide.contains(h) {
+                        e.hide.push(*h);
+                    }
+                }
+            }
+            if let Some(first) = expansion.first_mut() {
+                first.space = t.space;
+            }
+            input.extend(expansion.into_iter().rev());
+        }
+
+        output
+    }
+
+    // __FILE__ and __LINE__ where t appears, which is where the outermost
+    // macro was invoked when t comes from an expansion
+    fn builtin(&mut self, builtin: Builtin, t: PpToken) -> PpToken {
+        let location = self.source_map.location(t.tok.span);
+        let (text, typ) = match builtin {
+            Builtin::File => {
+                let name = location.name.replace('\\', "\\\\").replace('"', "\\\"");
+                (format!("\"{name}\""), TokenType::LiteralStr)
+            }
+            Builtin::Line => (location.line.to_string(), TokenType::LiteralInt),
+        };
+
+        PpToken {
+            tok: Token {
+                lexeme: Lexeme::Spelled(scratch(self.source_map, text)),
+                typ,
+                ..t.tok
+            },
+            ..t
+        }
+    }
+
+    // the comma separated arguments of an invocation, after its (, and the
+    // ) that closes them
+    fn args(&mut self, input: &mut Vec<PpToken>) -> Option<(Vec<Vec<PpToken>>, PpToken)> {
+        let mut args = vec![Vec::new()];
+        let mut depth = 0;
+        loop {
+            let t = input.pop()?;
+            match t.tok.typ {
+                TokenType::PuncRightParen if depth == 0 => return Some((args, t)),
+                TokenType::PuncComma if depth == 0 => {
+                    args.push(Vec::new());
+                    continue;
+                }
+                TokenType::PuncLeftParen => depth += 1,
+                TokenType::PuncRightParen => depth -= 1,
+                _ => (),
+            }
+            args.last_mut().unwrap().push(t);
+        }
+    }
+
+    // the body of a macro with its parameters replaced by arguments: fully
+    // expanded, or as written when they are operands of # and ##. tokens of
+    // the body take the span of the invocation
+    fn subst(&mut self, m: &Macro, args: &[Vec<PpToken>], span: Span) -> Vec<PpToken> {
+        let from_body = |t: &PpToken| PpToken {
+            tok: Token { span, ..t.tok },
+            ..t.clone()
+        };
+
+        let mut output: Vec<PpToken> = Vec::new();
+        let mut placemarker = false; // an empty left operand of ##
+        let mut i = 0;
+        while i < m.body.len() {
+            let t = &m.body[i];
+            let next = m.body.get(i + 1);
+
+            if t.is(TokenType::Hash) && m.params.is_some() {
+                let p = next.and_then(|n| m.param(n)).unwrap();
+                let mut s = stringize(self.source_map, &args[p], span);
+                s.space = t.space;
+                output.push(s);
+                i += 2;
+                continue;
+            }
+
+            if t.is(TokenType::HashHash) {
+                let rhs = next.unwrap();
+                let rhs = match m.param(rhs) {
+                    Some(p) => args[p].clone(),
+                    None => vec![from_body(rhs)],
+                };
+                let lhs = if placemarker { None } else { output.pop() };
+                match (lhs, rhs.split_first()) {
+                    (Some(lhs), Some((first, rest))) => {
+                        output.push(self.paste(&lhs, first, span));
+                        output.extend_from_slice(rest);
+                    }
+                    (lhs, _) => {
+                        output.extend(lhs);
+                        output.extend_from_slice(&rhs);
+                    }
+                }
+                // pasting two placemarkers leaves a placemarker
+                placemarker = placemarker && rhs.is_empty();
+                i += 2;
+                continue;
+            }
+
+            match m.param(t) {
+                Some(p) => {
+                    let pasted = next.is_some_and(|n| n.is(TokenType::HashHash));
+                    let mut arg = if pasted {
+                        args[p].clone()
+                    } else {
+                        self.expand(args[p].clone())
+                    };
+                    if let Some(first) = arg.first_mut() {
+                        first.space = t.space;
+                    }
+                    placemarker = pasted && arg.is_empty();
+                    output.extend(arg);
+                }
+                None => output.push(from_body(t)),
+            }
+            i += 1;
+        }
+
+        output
+    }
+
+    // the token ## makes of two, by lexing their text as one
+    fn paste(&mut self, lhs: &PpToken, rhs: &PpToken, span: Span) -> PpToken {
+        let (l, r) = (lhs.tok.text(self.source_map), rhs.tok.text(self.source_map));
+        let text = scratch(self.source_map, format!("{l}{r}"));
+        match Lexer::new(self.source_map.snippet(text), text.file)
+            .std(self.std)
+            .pp_tokens(true)
+            .tokenize()
+            .as_deref()
+        {
+            Ok([t]) => PpToken {
+                tok: Token { span, ..*t },
+                ..lhs.clone()
+            },
+            _ => {
+                self.errors.push(PreprocessError::InvalidPaste {
+                    lhs: lhs.tok.text(self.source_map).into_owned(),
+                    rhs: rhs.tok.text(self.source_map).into_owned(),
+                    span,
+                });
+                lhs.clone()
+            }
+        }
+    }
+}
+
+// where -E output moves into an #include, back out of it to the line after
+// the directive, or to the presumed file and line a #line gives
+#[derive(Clone, Debug)]
+enum Marker {
+    Enter(FileId),
+    Return(String, usize),
+    Line(String, usize),
+}
+
+// writes the lines of -E output
+struct Printer<'a> {
+    source_map: &'a SourceMap,
+    out: String,
+    name: String, // of the presumed file
+    line: usize,
+}
+
+impl Printer<'_> {
+    fn marker(&mut self, name: &str, line: usize, flags: &str) {
+        if !self.out.is_empty() && !self.out.ends_with('\n') {
+            self.out.push('\n');
+        }
+        self.out.push_str(&format!("# {line} \"{name}\"{flags}\n"));
+        self.name = name.to_owned();
+        self.line = line;
+    }
+
+    fn follow(&mut self, marker: Marker) {
+        match marker {
+            Marker::Enter(file) => {
+                let name = self.source_map.file(file).name().to_owned();
+                self.marker(&name, 1, " 1")
+            }
+            Marker::Return(name, line) => self.marker(&name, line, " 2"),
+            Marker::Line(name, line) => self.marker(&name, line, ""),
+        }
+    }
+
+    // moves the output to a line, returning whether it started a new line.
+    // short gaps are printed as blank lines, as gcc does
+    fn goto(&mut self, name: &str, line: usize) -> bool {
+        if name != self.name || line > self.line + 8 {
+            self.marker(name, line, "");
+        } else if line > self.line {
+            self.out.push_str(&"\n".repeat(line - self.line));
+            self.line = line;
+        }
+
+        self.out.ends_with('\n')
+    }
+}
+
+// whether printing two tokens next to each other would lex as something
+// else, like - followed by - or two identifiers
+fn would_paste(source_map: &SourceMap, l: Token, r: Token) -> bool {
+    let (l, r) = (l.text(source_map), r.text(source_map));
+    let text = format!("{l}{r}");
+    let lexer = Lexer::new(&text, FileId::default()).pp_tokens(true);
+    match lexer.tokenize().as_deref() {
+        Ok([a, _]) => a.span.hi != l.len(),
+        _ => true,
+    }
+}
+
+/// Preprocesses a file with no include directories.
+pub fn preprocess(
+    source_map: &mut SourceMap,
+    file: FileId,
+) -> Result<Vec<Token>, Vec<PreprocessError>> {
+    Preprocessor::new(source_map).preprocess(file)
+}
+
+#[cfg(test)]
+mod test_include {
+    use super::{test_util::located, Preprocessor};
+    use crate::source::SourceMap;
+
+    const TEST_DIR: &str = "tests/fixtures/din/legal/preprocessor/include";
+
+    #[test]
+    fn search_paths() {
+        let mut source_map = SourceMap::new();
+        let file = source_map.load(format!("{TEST_DIR}/main.c")).unwrap();
+
+        let output = Preprocessor::new(&mut source_map)
+            .include_dir(format!("{TEST_DIR}/lib"))
+            .system_dir(format!("{TEST_DIR}/system"))
+            .preprocess(file)
+            .unwrap();
+        let pairs = output
+            .iter()
+            .map(|t| (t.text(&source_map), source_map.file(t.span.file).name()))
+            .collect::<Vec<_>>();
+        insta::assert_yaml_snapshot!(pairs, @r###"
+        ---
+        - - int
+          - tests/fixtures/din/legal/preprocessor/include/main.c
+        - - main
+          - tests/fixtures/din/legal/preprocessor/include/main.c
+        - - (
+          - tests/fixtures/din/legal/preprocessor/include/main.c
+        - - )
+          - tests/fixtures/din/legal/preprocessor/include/main.c
+        - - "{"
+          - tests/fixtures/din/legal/preprocessor/include/main.c
+        - - int
+          - tests/fixtures/din/legal/preprocessor/include/guarded.h
+        - - a
+          - tests/fixtures/din/legal/preprocessor/include/guarded.h
+        - - "="
+          - tests/fixtures/din/legal/preprocessor/include/guarded.h
+        - - "1"
+          - tests/fixtures/din/legal/preprocessor/include/guarded.h
+        - - ;
+          - tests/fixtures/din/legal/preprocessor/include/guarded.h
+        - - int
+          - tests/fixtures/din/legal/preprocessor/include/once.h
+        - - b
+          - tests/fixtures/din/legal/preprocessor/include/once.h
+        - - "="
+          - tests/fixtures/din/legal/preprocessor/include/once.h
+        - - "2"
+          - tests/fixtures/din/legal/preprocessor/include/once.h
+        - - ;
+          - tests/fixtures/din/legal/preprocessor/include/once.h
+        - - int
+          - tests/fixtures/din/legal/preprocessor/include/lib/lib.h
+        - - c
+          - tests/fixtures/din/legal/preprocessor/include/lib/lib.h
+        - - "="
+          - tests/fixtures/din/legal/preprocessor/include/lib/lib.h
+        - - "3"
+          - tests/fixtures/din/legal/preprocessor/include/lib/lib.h
+        - - ;
+          - tests/fixtures/din/legal/preprocessor/include/lib/lib.h
+        - - int
+          - tests/fixtures/din/legal/preprocessor/include/system/sys.h
+        - - d
+          - tests/fixtures/din/legal/preprocessor/include/system/sys.h
+        - - "="
+          - tests/fixtures/din/legal/preprocessor/include/system/sys.h
+        - - "36"
+          - tests/fixtures/din/legal/preprocessor/include/system/sys.h
+        - - ;
+          - tests/fixtures/din/legal/preprocessor/include/system/sys.h
+        - - return
+          - tests/fixtures/din/legal/preprocessor/include/main.c
+        - - a
+          - tests/fixtures/din/legal/preprocessor/include/main.c
+        - - +
+          - tests/fixtures/din/legal/preprocessor/include/main.c
+        - - b
+          - tests/fixtures/din/legal/preprocessor/include/main.c
+        - - +
+          - tests/fixtures/din/legal/preprocessor/include/main.c
+        - - c
+          - tests/fixtures/din/legal/preprocessor/include/main.c
+        - - +
+          - tests/fixtures/din/legal/preprocessor/include/main.c
+        - - d
+          - tests/fixtures/din/legal/preprocessor/include/main.c
+        - - ;
+          - tests/fixtures/din/legal/preprocessor/include/main.c
+        - - "}"
+          - tests/fixtures/din/legal/preprocessor/include/main.c
+        "###);
+    }
+
+    #[test]
+    fn system_dir_only_for_angle_brackets() {
+        let mut source_map = SourceMap::new();
+        let file = source_map.load(format!("{TEST_DIR}/main.c")).unwrap();
+
+        let errors = Preprocessor::new(&mut source_map)
+            .include_dir(format!("{TEST_DIR}/lib"))
+            .preprocess(file)
+            .unwrap_err();
+        insta::assert_yaml_snapshot!(located(&source_map, &errors), @r###"
+        ---
+        - "tests/fixtures/din/legal/preprocessor/include/main.c:7:10: sys.h: No such file or directory"
+        "###);
+    }
+}
+
+#[cfg(test)]
+mod test_util {
+    use super::{preprocess, PreprocessError};
+    use crate::source::SourceMap;
+
+    // the errors preprocessing a file reports, as file:line:col: message
+    pub fn errors(dir: &str, path: &str) -> Vec<String> {
+        let mut source_map = SourceMap::new();
+        let file = source_map.load(format!("{dir}/{path}")).unwrap();
+
+        let errors = preprocess(&mut source_map, file).unwrap_err();
+        located(&source_map, &errors)
+    }
+
+    pub fn located(source_map: &SourceMap, errors: &[PreprocessError]) -> Vec<String> {
+        errors
+            .iter()
+            .map(|e| format!("{}: {e}", source_map.location(e.span())))
+            .collect()
+    }
+
+    // the tokens a file expands to, separated by spaces
+    pub fn expand(dir: &str, path: &str) -> String {
+        let mut source_map = SourceMap::new();
+        let file = source_map.load(format!("{dir}/{path}")).unwrap();
+
+        let tokens = preprocess(&mut source_map, file).unwrap();
+        tokens
+            .iter()
+            .map(|t| t.text(&source_map))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
+
+#[cfg(test)]
+mod test_illegal {
+    use super::test_util::errors;
+
+    const TEST_DIR: &str = "tests/fixtures/din/illegal/include";
+
+    #[test]
+    fn cycle() {
+        insta::assert_yaml_snapshot!(errors(TEST_DIR, "cycle.c"), @r###"
+        ---
+        - "tests/fixtures/din/illegal/include/b.h:1:10: #include cycle: a.h includes itself"
+        "###);
+    }
+
+    #[test]
+    fn missing() {
+        insta::assert_yaml_snapshot!(errors(TEST_DIR, "missing.c"), @r###"
+        ---
+        - "tests/fixtures/din/illegal/include/missing.c:2:10: missing.h: No such file or directory"
+        "###);
+    }
+
+    #[test]
+    fn malformed() {
+        insta::assert_yaml_snapshot!(errors(TEST_DIR, "malformed.c"), @r###"
+        ---
+        - "tests/fixtures/din/illegal/include/malformed.c:2:1: #include expects \"FILENAME\" or <FILENAME>"
+        "###);
+    }
+}
+
+#[cfg(test)]
+mod test_macros {
+    const TEST_DIR: &str = "tests/fixtures/din/legal/preprocessor/macros";
+
+    // the expanded text, one line per ;
+    fn expand(path: &str) -> Vec<String> {
+        let text = super::test_util::expand(TEST_DIR, path);
+        text.split_inclusive(';')
+            .map(|s| s.trim().to_owned())
+            .collect()
+    }
+
+    #[test]
+    fn rescan() {
+        insta::assert_yaml_snapshot!(expand("rescan.c"), @r###"
+        ---
+        - "f ( 2 * ( y + 1 ) ) + f ( 2 * ( f ( 2 * ( z [ 0 ] ) ) ) ) % f ( 2 * ( 0 ) ) + t ( 1 ) ;"
+        - "f ( 2 * ( 2 + ( 3 , 4 ) - 0 , 1 ) ) | f ( 2 * ( ~ 5 ) ) & f ( 2 * ( 0 , 1 ) ) ^ m ( 0 , 1 ) ;"
+        - "int i [ ] = { 1 , 23 , 4 , 5 , } ;"
+        - "char c [ 2 ] [ 6 ] = { \"hello\" , \"\" } ;"
+        - "int j [ ] = { 123 , 45 , 67 , 89 , 10 , 11 , 12 , } ;"
+        "###);
+    }
+
+    #[test]
+    fn stringify() {
+        insta::assert_yaml_snapshot!(expand("stringify.c"), @r###"
+        ---
+        - "printf ( \"x\" \"1\" \"= %d, x\" \"2\" \"= %s\" , x1 , x2 ) ;"
+        - "fputs ( \"strncmp(\\\"abc\\\\0d\\\", \\\"abc\\\", '\\\\4') == 0\" \": \\\"\\\\n\\\"\" , s ) ;"
+        - "\"vers2.h\" \"hello\" ;"
+        - "\"hello\" \", world\""
+        "###);
+    }
+
+    #[test]
+    fn regs() {
+        insta::assert_yaml_snapshot!(expand("regs.c"), @r###"
+        ---
+        - "int main ( ) { int reg = ( 0x10 + ( 2 + 1 ) * 4 ) ;"
+        - return reg ;
+        - "}"
+        "###);
+    }
+}
+
+#[cfg(test)]
+mod test_illegal_macros {
+    use super::test_util::errors;
+
+    const TEST_DIR: &str = "tests/fixtures/din/illegal/macros";
+
+    #[test]
+    fn arg_count() {
+        insta::assert_yaml_snapshot!(errors(TEST_DIR, "arg_count.c"), @r###"
+        ---
+        - "tests/fixtures/din/illegal/macros/arg_count.c:3:10: macro f requires 2 arguments, but 1 given"
+        "###);
+    }
+
+    #[test]
+    fn bad_paste() {
+        insta::assert_yaml_snapshot!(errors(TEST_DIR, "bad_paste.c"), @r###"
+        ---
+        - "tests/fixtures/din/illegal/macros/bad_paste.c:3:10: pasting + and / does not give a valid preprocessing token"
+        "###);
+    }
+
+    #[test]
+    fn pp_number() {
+        insta::assert_yaml_snapshot!(errors(TEST_DIR, "pp_number.c"), @r###"
+        ---
+        - "tests/fixtures/din/illegal/macros/pp_number.c:2:9: invalid digit '8' in octal constant"
+        "###);
+    }
+
+    #[test]
+    fn redefined() {
+        insta::assert_yaml_snapshot!(errors(TEST_DIR, "redefined.c"), @r###"
+        ---
+        - "tests/fixtures/din/illegal/macros/redefined.c:2:9: N redefined"
+        "###);
+    }
+
+    #[test]
+    fn stringify_param() {
+        insta::assert_yaml_snapshot!(errors(TEST_DIR, "stringify_param.c"), @r###"
+        ---
+        - "tests/fixtures/din/illegal/macros/stringify_param.c:1:16: '#' is not followed by a macro parameter"
+        "###);
+    }
+
+    #[test]
+    fn unterminated() {
+        insta::assert_yaml_snapshot!(errors(TEST_DIR, "unterminated.c"), @r###"
+        ---
+        - "tests/fixtures/din/illegal/macros/unterminated.c:3:10: unterminated argument list invoking macro f"
+        "###);
+    }
+
+    #[test]
+    fn params() {
+        insta::assert_yaml_snapshot!(errors(TEST_DIR, "params.c"), @r###"
+        ---
+        - "tests/fixtures/din/illegal/macros/params.c:1:14: duplicate macro parameter a"
+        - "tests/fixtures/din/illegal/macros/params.c:2:12: invalid macro parameter list"
+        - "tests/fixtures/din/illegal/macros/params.c:3:1: macro names must be identifiers"
+        "###);
+    }
+}
+
+#[cfg(test)]
+mod test_conditional {
+    use super::test_util::expand;
+
+    const TEST_DIR: &str = "tests/fixtures/din/legal/preprocessor/conditional";
+
+    #[test]
+    fn if_elif_else() {
+        insta::assert_yaml_snapshot!(expand(TEST_DIR, "if.c"), @r###"
+        ---
+        int a ; int b ; int c ; int d ; int e ; int f ;
+        "###);
+    }
+
+    #[test]
+    fn config() {
+        insta::assert_yaml_snapshot!(expand(TEST_DIR, "config.c"), @r###"
+        ---
+        "int main ( ) { return 42 ; }"
+        "###);
+    }
+}
+
+#[cfg(test)]
+mod test_illegal_conditional {
+    use super::test_util::errors;
+
+    const TEST_DIR: &str = "tests/fixtures/din/illegal/conditional";
+
+    #[test]
+    fn unterminated() {
+        insta::assert_yaml_snapshot!(errors(TEST_DIR, "unterminated.c"), @r###"
+        ---
+        - "tests/fixtures/din/illegal/conditional/unterminated.c:1:1: unterminated #if"
+        "###);
+    }
+
+    #[test]
+    fn unmatched() {
+        insta::assert_yaml_snapshot!(errors(TEST_DIR, "unmatched.c"), @r###"
+        ---
+        - "tests/fixtures/din/illegal/conditional/unmatched.c:1:1: #else without #if"
+        - "tests/fixtures/din/illegal/conditional/unmatched.c:2:1: #endif without #if"
+        "###);
+    }
+
+    #[test]
+    fn after_else() {
+        insta::assert_yaml_snapshot!(errors(TEST_DIR, "after_else.c"), @r###"
+        ---
+        - "tests/fixtures/din/illegal/conditional/after_else.c:3:1: #elif after #else"
+        - "tests/fixtures/din/illegal/conditional/after_else.c:4:1: #else after #else"
+        "###);
+    }
+
+    #[test]
+    fn div_zero() {
+        insta::assert_yaml_snapshot!(errors(TEST_DIR, "div_zero.c"), @r###"
+        ---
+        - "tests/fixtures/din/illegal/conditional/div_zero.c:1:7: division by zero in #if"
+        "###);
+    }
+
+    #[test]
+    fn bad_expr() {
+        insta::assert_yaml_snapshot!(errors(TEST_DIR, "bad_expr.c"), @r###"
+        ---
+        - "tests/fixtures/din/illegal/conditional/bad_expr.c:1:1: #if with no expression"
+        - "tests/fixtures/din/illegal/conditional/bad_expr.c:3:1: #if with no expression"
+        - "tests/fixtures/din/illegal/conditional/bad_expr.c:5:1: expected ')' in preprocessor expression"
+        - "tests/fixtures/din/illegal/conditional/bad_expr.c:7:7: missing binary operator before token 2"
+        - "tests/fixtures/din/illegal/conditional/bad_expr.c:9:5: operator \"defined\" requires an identifier"
+        - "tests/fixtures/din/illegal/conditional/bad_expr.c:11:5: token 1.5 is not valid in preprocessor expressions"
+        - "tests/fixtures/din/illegal/conditional/bad_expr.c:13:5: integer constant is too large for its type"
+        - "tests/fixtures/din/illegal/conditional/bad_expr.c:15:5: missing terminating ' character"
+        "###);
+    }
+}
+
+#[cfg(test)]
+mod test_directives {
+    use super::Preprocessor;
+    use crate::source::SourceMap;
+
+    const TEST_DIR: &str = "tests/fixtures/din/legal/preprocessor/directives";
+
+    #[test]
+    fn line() {
+        let mut source_map = SourceMap::new();
+        let file = source_map.load(format!("{TEST_DIR}/line.c")).unwrap();
+
+        let mut warnings = Vec::new();
+        let output = Preprocessor::new(&mut source_map)
+            .warnings(&mut warnings)
+            .preprocess(file)
+            .unwrap();
+        let tokens = output
+            .iter()
+            .map(|t| {
+                format!(
+                    "{}: {:?} {}",
+                    source_map.location(t.span),
+                    t.typ,
+                    t.text(&source_map)
+                )
+            })
+            .collect::<Vec<_>>();
+        insta::assert_yaml_snapshot!(tokens, @r###"
+        ---
+        - "tests/fixtures/din/legal/preprocessor/directives/line.c:2:1: Pragma STDC FP_CONTRACT ON"
+        - "tests/fixtures/din/legal/preprocessor/directives/line.c:3:1: Pragma pack(4)"
+        - "tests/fixtures/din/legal/preprocessor/directives/line.c:7:1: KeywordInt int"
+        - "tests/fixtures/din/legal/preprocessor/directives/line.c:7:5: Identifier a"
+        - "tests/fixtures/din/legal/preprocessor/directives/line.c:7:7: Equals ="
+        - "tests/fixtures/din/legal/preprocessor/directives/line.c:7:9: LiteralInt 7"
+        - "tests/fixtures/din/legal/preprocessor/directives/line.c:7:17: PuncSemiColon ;"
+        - "tests/fixtures/din/legal/preprocessor/directives/line.c:100:1: KeywordInt int"
+        - "tests/fixtures/din/legal/preprocessor/directives/line.c:100:5: Identifier b"
+        - "tests/fixtures/din/legal/preprocessor/directives/line.c:100:7: Equals ="
+        - "tests/fixtures/din/legal/preprocessor/directives/line.c:100:9: LiteralInt 100"
+        - "tests/fixtures/din/legal/preprocessor/directives/line.c:100:17: PuncSemiColon ;"
+        - "generated.c:200:1: KeywordConst const"
+        - "generated.c:200:7: KeywordChar char"
+        - "generated.c:200:12: Star *"
+        - "generated.c:200:13: Identifier f"
+        - "generated.c:200:15: Equals ="
+        - "generated.c:200:17: LiteralStr \"generated.c\""
+        - "generated.c:200:25: PuncSemiColon ;"
+        - "generated.c:201:1: KeywordInt int"
+        - "generated.c:201:5: Identifier c"
+        - "generated.c:201:7: Equals ="
+        - "generated.c:201:9: LiteralInt 201"
+        - "generated.c:201:17: PuncSemiColon ;"
+        - "generated.c:300:1: KeywordInt int"
+        - "generated.c:300:5: Identifier d"
+        - "generated.c:300:7: Equals ="
+        - "generated.c:300:9: LiteralInt 300"
+        - "generated.c:300:17: PuncSemiColon ;"
+        "###);
+
+        let warnings = warnings
+            .iter()
+            .map(|w| format!("{}: {w}", source_map.location(w.span())))
+            .collect::<Vec<_>>();
+        insta::assert_yaml_snapshot!(warnings, @r###"
+        ---
+        - "tests/fixtures/din/legal/preprocessor/directives/line.c:4:1: ignoring #pragma din unroll"
+        - "tests/fixtures/din/legal/preprocessor/directives/line.c:5:1: #warning don't ship this"
+        "###);
+    }
+
+    #[test]
+    fn warning_splices() {
+        let mut source_map = SourceMap::new();
+        let file = source_map.load(format!("{TEST_DIR}/warning.c")).unwrap();
+
+        let mut warnings = Vec::new();
+        Preprocessor::new(&mut source_map)
+            .warnings(&mut warnings)
+            .preprocess(file)
+            .unwrap();
+        let warnings = warnings
+            .iter()
+            .map(|w| format!("{}: {w}", source_map.location(w.span())))
+            .collect::<Vec<_>>();
+        insta::assert_yaml_snapshot!(warnings, @r###"
+        ---
+        - "tests/fixtures/din/legal/preprocessor/directives/warning.c:1:1: #warning split across lines"
+        - "tests/fixtures/din/legal/preprocessor/directives/warning.c:3:1: #warning and by a trigraph"
+        "###);
+    }
+}
+
+#[cfg(test)]
+mod test_illegal_directives {
+    use super::{
+        test_util::{errors, located},
+        Preprocessor,
+    };
+    use crate::{lexer::Std, source::SourceMap};
+
+    const TEST_DIR: &str = "tests/fixtures/din/illegal/directives";
+
+    #[test]
+    fn error() {
+        insta::assert_yaml_snapshot!(errors(TEST_DIR, "error.c"), @r###"
+        ---
+        - "tests/fixtures/din/illegal/directives/error.c:4:1: #error din can't target rv64: XLEN is XLEN"
+        "###);
+    }
+
+    #[test]
+    fn line() {
+        let mut source_map = SourceMap::new();
+        let file = source_map.load(format!("{TEST_DIR}/line.c")).unwrap();
+
+        let errors = Preprocessor::new(&mut source_map)
+            .std(Std::C89)
+            .preprocess(file)
+            .unwrap_err();
+        insta::assert_yaml_snapshot!(located(&source_map, &errors), @r###"
+        ---
+        - "tests/fixtures/din/illegal/directives/line.c:1:7: \"0\" after #line is not a positive integer"
+        - "tests/fixtures/din/illegal/directives/line.c:2:7: \"0x10\" after #line is not a positive integer"
+        - "tests/fixtures/din/illegal/directives/line.c:3:7: line number out of range"
+        - "tests/fixtures/din/illegal/directives/line.c:4:1: #line expects a line number and an optional \"FILENAME\""
+        - "tests/fixtures/din/illegal/directives/line.c:5:1: #line expects a line number and an optional \"FILENAME\""
+        - "tests/fixtures/din/illegal/directives/line.c:6:1: #line expects a line number and an optional \"FILENAME\""
+        "###);
+    }
+}
+
+#[cfg(test)]
+mod test_predefined {
+    use super::Preprocessor;
+    use crate::{source::SourceMap, target::Target};
+
+    const TEST_DIR: &str = "tests/fixtures/din/legal/preprocessor/predefined";
+
+    fn expand(pp: impl FnOnce(Preprocessor) -> Preprocessor) -> Vec<String> {
+        let mut source_map = SourceMap::new();
+        let file = source_map.load(format!("{TEST_DIR}/predefined.c")).unwrap();
+
+        let tokens = pp(Preprocessor::new(&mut source_map))
+            .preprocess(file)
+            .unwrap();
+        let text = tokens
+            .iter()
+            .map(|t| t.text(&source_map))
+            .collect::<Vec<_>>()
+            .join(" ");
+        text.split_inclusive(';')
+            .map(|s| s.trim().to_owned())
+            .collect()
+    }
+
+    #[test]
+    fn command_line() {
+        let output = expand(|pp| {
+            pp.define("NDEBUG")
+                .define("LEVEL=3")
+                .define("DEBUG")
+                .undef("DEBUG")
+                .timestamp(1700000000)
+        });
+        insta::assert_yaml_snapshot!(output, @r###"
+        ---
+        - int a = 2 ;
+        - "char * f = \"tests/fixtures/din/legal/preprocessor/predefined/predefined.c\" ;"
+        - "char * d = \"Nov 14 2023\" ;"
+        - "char * t = \"22:13:20\" ;"
+        - int n = 3 ;
+        - int l = 15 ;
+        "###);
+    }
+
+    #[test]
+    fn target() {
+        let output = expand(|pp| pp.target(Target::parse("rv32i").unwrap()));
+        insta::assert_yaml_snapshot!(output, @r###"
+        ---
+        - int l = 15 ;
+        "###);
+    }
+
+    #[test]
+    fn date_time() {
+        assert_eq!(
+            super::date_time(0),
+            ("Jan  1 1970".to_owned(), "00:00:00".to_owned())
+        );
+        assert_eq!(
+            super::date_time(951782400), // a leap day
+            ("Feb 29 2000".to_owned(), "00:00:00".to_owned())
+        );
+    }
+}
+
+#[cfg(test)]
+mod test_print {
+    use super::Preprocessor;
+    use crate::source::SourceMap;
+
+    const TEST_DIR: &str = "tests/fixtures/din/legal/preprocessor";
+
+    fn print(path: &str) -> String {
+        let mut source_map = SourceMap::new();
+        let file = source_map.load(format!("{TEST_DIR}/{path}")).unwrap();
+
+        Preprocessor::new(&mut source_map)
+            .include_dir(format!("{TEST_DIR}/include/lib"))
+            .system_dir(format!("{TEST_DIR}/include/system"))
+            .preprocess_to_text(file)
+            .unwrap()
+    }
+
+    #[test]
+    fn include_markers() {
+        insta::assert_snapshot!(print("include/main.c"), @r###"
+        # 1 "tests/fixtures/din/legal/preprocessor/include/main.c"
+        int main() {
+        # 1 "tests/fixtures/din/legal/preprocessor/include/guarded.h" 1
+
+
+
+          int a = 1;
+        # 3 "tests/fixtures/din/legal/preprocessor/include/main.c" 2
+        # 1 "tests/fixtures/din/legal/preprocessor/include/once.h" 1
+
+
+          int b = 2;
+        # 5 "tests/fixtures/din/legal/preprocessor/include/main.c" 2
+        # 1 "tests/fixtures/din/legal/preprocessor/include/lib/lib.h" 1
+          int c = 3;
+        # 7 "tests/fixtures/din/legal/preprocessor/include/main.c" 2
+        # 1 "tests/fixtures/din/legal/preprocessor/include/system/sys.h" 1
+          int d = 36;
+        # 8 "tests/fixtures/din/legal/preprocessor/include/main.c" 2
+          return a + b + c + d;
+        }
+        "###);
+    }
+
+    #[test]
+    fn guard_with_else() {
+        insta::assert_snapshot!(print("include/else.c"), @r###"
+        # 1 "tests/fixtures/din/legal/preprocessor/include/else.c"
+        # 1 "tests/fixtures/din/legal/preprocessor/include/guarded_else.h" 1
+
+
+          int first;
+        # 2 "tests/fixtures/din/legal/preprocessor/include/else.c" 2
+        # 1 "tests/fixtures/din/legal/preprocessor/include/guarded_else.h" 1
+
+
+
+
+          int second;
+        # 3 "tests/fixtures/din/legal/preprocessor/include/else.c" 2
+        "###);
+    }
+
+    #[test]
+    fn expansion_spacing() {
+        insta::assert_snapshot!(print("macros/rescan.c"), @r###"
+        # 1 "tests/fixtures/din/legal/preprocessor/macros/rescan.c"
+        # 15 "tests/fixtures/din/legal/preprocessor/macros/rescan.c"
+        f(2 * (y+1)) + f(2 * (f(2 * (z[0])))) % f(2 * (0)) + t(1);
+        f(2 * (2+(3,4)-0,1)) | f(2 * (~ 5)) &
+             f(2 * (0,1))^m(0,1);
+        int i[] = { 1, 23, 4, 5, };
+        char c[2][6] = { "hello", "" };
+
+
+        int j[] = { 123, 45, 67, 89,
+            10, 11, 12, };
+        "###);
+    }
+
+    #[test]
+    fn pp_numbers() {
+        insta::assert_snapshot!(print("macros/pp_numbers.c"), @r###"
+        # 1 "tests/fixtures/din/legal/preprocessor/macros/pp_numbers.c"
+
+
+
+        "1.2.3" "0x" "08" "'\\q'"
+        "###);
+    }
+
+    #[test]
+    fn line_markers() {
+        insta::assert_snapshot!(print("directives/line.c"), @r###"
+        # 1 "tests/fixtures/din/legal/preprocessor/directives/line.c"
+
+        #pragma STDC FP_CONTRACT ON
+        #pragma pack(4)
+
+
+
+        int a = 7;
+        # 100 "tests/fixtures/din/legal/preprocessor/directives/line.c"
+        int b = 100;
+        # 200 "generated.c"
+        const char *f = "generated.c";
+        int c = 201;
+        # 300 "generated.c"
+        int d = 300;
+        "###);
+    }
+
+    #[test]
+    fn skipped_lines() {
+        insta::assert_snapshot!(print("conditional/if.c"), @r###"
+        # 1 "tests/fixtures/din/legal/preprocessor/conditional/if.c"
+
+
+
+        int a;
+
+
+
+
+        int b;
+        # 21 "tests/fixtures/din/legal/preprocessor/conditional/if.c"
+        int c;
+
+
+
+
+        int d;
+
+
+
+
+        int e;
+
+
+        int f;
+        "###);
+    }
+}
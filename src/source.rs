@@ -0,0 +1,234 @@
+use crate::splice::splice;
+use serde::{Deserialize, Serialize};
+use std::{borrow::Cow, fmt, fs, io, path::Path};
+
+/// Identifies a file in the `SourceMap`.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Default, Serialize, Deserialize, Debug)]
+pub struct FileId(pub u32);
+
+/// A half-open byte range [lo, hi) of a file in the `SourceMap`. Spans are
+/// cheap to copy around, and only turned into (line, col) when a diagnostic
+/// is actually printed.
+#[derive(Copy, Clone, PartialEq, Eq, Default, Serialize, Deserialize, Debug)]
+pub struct Span {
+    pub file: FileId,
+    pub lo: usize,
+    pub hi: usize,
+}
+
+/// A human readable position: file name and 1-based (line, col), where col
+/// counts characters rather than bytes.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Location {
+    pub name: String,
+    pub line: usize,
+    pub col: usize,
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}:{}", self.name, self.line, self.col)
+    }
+}
+
+pub struct SourceFile {
+    name: String,
+    src: String,
+    line_starts: Vec<usize>, // byte offset of the first character of each line
+    remaps: Vec<Remap>,      // from #line, in order
+}
+
+// a #line directive: physical line `first` (0-based) and those after it are
+// presumed to be lines `line`, `line + 1`, ... of the file `name`
+struct Remap {
+    first: usize,
+    line: usize,
+    name: String,
+}
+
+impl SourceFile {
+    fn new(name: String, src: String) -> Self {
+        #[rustfmt::skip]
+        let line_starts = std::iter::once(0)
+            .chain(src.match_indices('\n').map(|(i, _)| i + 1))
+            .collect();
+
+        SourceFile {
+            name,
+            src,
+            line_starts,
+            remaps: Vec::new(),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn src(&self) -> &str {
+        &self.src
+    }
+
+    /// The 1-based (line, col) of a byte offset.
+    pub fn line_col(&self, offset: usize) -> (usize, usize) {
+        let line = self.line_starts.partition_point(|&start| start <= offset) - 1;
+        let col = self.src[self.line_starts[line]..offset].chars().count();
+        (line + 1, col + 1)
+    }
+
+    // the file name and 1-based line a physical 0-based line is presumed to
+    // be at, after any #line directives before it
+    fn presumed(&self, line: usize) -> (&str, usize) {
+        match self.remaps.partition_point(|r| r.first <= line) {
+            0 => (&self.name, line + 1),
+            i => {
+                let r = &self.remaps[i - 1];
+                (&r.name, r.line + (line - r.first))
+            }
+        }
+    }
+}
+
+/// Owns the text of every file in a compilation, so that the rest of the
+/// compiler can refer to source text by `Span` alone.
+#[derive(Default)]
+pub struct SourceMap {
+    files: Vec<SourceFile>,
+}
+
+impl SourceMap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Reads a file from disk. C source is expected to be UTF-8 (which
+    /// includes plain ASCII).
+    pub fn load(&mut self, path: impl AsRef<Path>) -> io::Result<FileId> {
+        let path = path.as_ref();
+        let src = fs::read_to_string(path)?;
+        Ok(self.add(path.display().to_string(), src))
+    }
+
+    pub fn add(&mut self, name: impl Into<String>, src: impl Into<String>) -> FileId {
+        self.files.push(SourceFile::new(name.into(), src.into()));
+        FileId(self.files.len() as u32 - 1)
+    }
+
+    pub fn file(&self, id: FileId) -> &SourceFile {
+        &self.files[id.0 as usize]
+    }
+
+    pub fn snippet(&self, span: Span) -> &str {
+        &self.file(span.file).src[span.lo..span.hi]
+    }
+
+    /// The text a span spells after translation phases 1 and 2, which is
+    /// the snippet itself unless a trigraph or backslash-newline is in it.
+    pub fn spelling(&self, span: Span) -> Cow<'_, str> {
+        let snippet = self.snippet(span);
+        if snippet.contains("??") || snippet.contains("\\\n") || snippet.contains("\\\r\n") {
+            Cow::Owned(splice(snippet).text().to_owned())
+        } else {
+            Cow::Borrowed(snippet)
+        }
+    }
+
+    /// Where a span is presumed to be, which is its physical location
+    /// unless a #line directive says otherwise.
+    pub fn location(&self, span: Span) -> Location {
+        let file = self.file(span.file);
+        let (line, col) = file.line_col(span.lo);
+        let (name, line) = file.presumed(line - 1);
+
+        Location {
+            name: name.to_owned(),
+            line,
+            col,
+        }
+    }
+
+    /// Makes the lines after the one containing `offset` presumed to be
+    /// lines `line` onwards of `name`, or of the current presumed file if
+    /// there is no name, as `#line` does.
+    pub fn remap_lines(&mut self, file: FileId, offset: usize, line: usize, name: Option<String>) {
+        let f = &mut self.files[file.0 as usize];
+        let first = f.line_starts.partition_point(|&start| start <= offset);
+        let name = name.unwrap_or_else(|| f.presumed(first).0.to_owned());
+        f.remaps.push(Remap { first, line, name });
+    }
+}
+
+#[cfg(test)]
+mod test_source_map {
+    use super::{SourceMap, Span};
+    use std::borrow::Cow;
+
+    #[test]
+    fn location() {
+        let mut sm = SourceMap::new();
+        let a = sm.add("a.c", "int main() {\n  return 0;\n}\n");
+        let b = sm.add("b.c", "/* é */ int x;");
+
+        let span = Span {
+            file: a,
+            lo: 15,
+            hi: 21,
+        };
+        assert_eq!(sm.snippet(span), "return");
+        assert_eq!(sm.location(span).to_string(), "a.c:2:3");
+
+        // columns count characters, not bytes
+        let span = Span {
+            file: b,
+            lo: 9,
+            hi: 12,
+        };
+        assert_eq!(sm.snippet(span), "int");
+        assert_eq!(sm.location(span).to_string(), "b.c:1:9");
+    }
+
+    #[test]
+    fn spelling() {
+        let mut sm = SourceMap::new();
+        let a = sm.add("a.c", "ret\\\nurn ??=x \"\\n\"");
+        let at = |lo, hi| Span { file: a, lo, hi };
+
+        assert_eq!(sm.spelling(at(0, 8)), "return");
+        assert_eq!(sm.spelling(at(9, 12)), "#");
+        assert_eq!(sm.spelling(at(14, 18)), "\"\\n\"");
+        assert!(matches!(sm.spelling(at(14, 18)), Cow::Borrowed(_)));
+    }
+
+    #[test]
+    fn end_of_file() {
+        let mut sm = SourceMap::new();
+        let a = sm.add("a.c", "x\n");
+
+        let span = Span {
+            file: a,
+            lo: 2,
+            hi: 2,
+        };
+        assert_eq!(sm.location(span).to_string(), "a.c:2:1");
+    }
+
+    #[test]
+    fn remap_lines() {
+        let mut sm = SourceMap::new();
+        let a = sm.add("a.c", "a\n#line 10\nb\nc\n#line 1 \"b.c\"\nd\n");
+        let at = |lo| Span {
+            file: a,
+            lo,
+            hi: lo,
+        };
+
+        sm.remap_lines(a, 10, 10, None);
+        assert_eq!(sm.location(at(0)).to_string(), "a.c:1:1");
+        assert_eq!(sm.location(at(2)).to_string(), "a.c:2:1");
+        assert_eq!(sm.location(at(13)).to_string(), "a.c:11:1");
+
+        sm.remap_lines(a, 28, 1, Some("b.c".to_owned()));
+        assert_eq!(sm.location(at(29)).to_string(), "b.c:1:1");
+        assert_eq!(sm.location(at(11)).to_string(), "a.c:10:1");
+    }
+}
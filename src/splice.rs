@@ -0,0 +1,185 @@
+use crate::source::{FileId, Span};
+use std::borrow::Cow;
+
+/// The text of a file after translation phases 1 and 2: trigraphs replaced
+/// and backslash-newlines deleted. Offsets into the spliced text are mapped
+/// back to the physical file so diagnostics still point at what the user
+/// wrote.
+pub struct Spliced<'a> {
+    text: Cow<'a, str>,
+    segments: Vec<Segment>,
+}
+
+// a run of spliced text starting at `lo` that maps linearly onto the
+// physical file starting at `orig`, except for a trigraph, whose single
+// character stands for three physical ones
+#[derive(Copy, Clone, Debug)]
+struct Segment {
+    lo: usize,
+    orig: usize,
+    trigraph: bool,
+}
+
+fn trigraph(c: char) -> Option<char> {
+    match c {
+        '=' => Some('#'),
+        '(' => Some('['),
+        '/' => Some('\\'),
+        ')' => Some(']'),
+        '\'' => Some('^'),
+        '<' => Some('{'),
+        '!' => Some('|'),
+        '>' => Some('}'),
+        '-' => Some('~'),
+        _ => None,
+    }
+}
+
+// the character at the start of src after trigraph replacement, and how
+// many physical bytes it took up
+fn phase1(src: &str) -> Option<(char, usize, bool)> {
+    let mut chars = src.chars();
+    match (chars.next()?, chars.next(), chars.next().and_then(trigraph)) {
+        ('?', Some('?'), Some(c)) => Some((c, 3, true)),
+        (c, _, _) => Some((c, c.len_utf8(), false)),
+    }
+}
+
+// the length of the newline at the start of src, if any
+fn newline(src: &str) -> Option<usize> {
+    if src.starts_with('\n') {
+        Some(1)
+    } else if src.starts_with("\r\n") {
+        Some(2)
+    } else {
+        None
+    }
+}
+
+pub fn splice(src: &str) -> Spliced<'_> {
+    let mut text = String::new();
+    let mut segments = vec![Segment {
+        lo: 0,
+        orig: 0,
+        trigraph: false,
+    }];
+
+    let mut offset = 0;
+    while let Some((c, len, is_trigraph)) = phase1(&src[offset..]) {
+        if c == '\\' {
+            if let Some(nl) = newline(&src[offset + len..]) {
+                offset += len + nl;
+                continue;
+            }
+        }
+
+        let last = segments[segments.len() - 1];
+        if is_trigraph || last.trigraph || last.orig + (text.len() - last.lo) != offset {
+            segments.push(Segment {
+                lo: text.len(),
+                orig: offset,
+                trigraph: is_trigraph,
+            });
+        }
+        text.push(c);
+        offset += len;
+    }
+
+    if segments.len() == 1 {
+        return Spliced {
+            text: Cow::Borrowed(src),
+            segments,
+        };
+    }
+
+    Spliced {
+        text: Cow::Owned(text),
+        segments,
+    }
+}
+
+impl Spliced<'_> {
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// The physical span of the spliced range [lo, hi).
+    pub fn span(&self, file: FileId, lo: usize, hi: usize) -> Span {
+        Span {
+            file,
+            lo: self.lo(lo),
+            hi: if hi > lo { self.hi(hi) } else { self.lo(hi) },
+        }
+    }
+
+    // the physical offset of the character starting at spliced offset o
+    fn lo(&self, o: usize) -> usize {
+        let i = self.segments.partition_point(|s| s.lo <= o) - 1;
+        let s = self.segments[i];
+        s.orig + (o - s.lo)
+    }
+
+    // the physical offset just past the character ending at spliced offset
+    // o, which excludes any backslash-newline that follows it
+    fn hi(&self, o: usize) -> usize {
+        let i = self.segments.partition_point(|s| s.lo < o) - 1;
+        let s = self.segments[i];
+        if s.trigraph {
+            s.orig + 3
+        } else {
+            s.orig + (o - s.lo)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test_splice {
+    use super::splice;
+    use crate::source::FileId;
+
+    #[test]
+    fn untouched() {
+        let spliced = splice("int main() {}");
+        assert_eq!(spliced.text(), "int main() {}");
+        assert_eq!(spliced.span(FileId(0), 4, 8).lo, 4);
+    }
+
+    #[test]
+    fn trigraphs() {
+        let spliced = splice("??=??(??/??)??'??<??!??>??-??x???=");
+        assert_eq!(spliced.text(), "#[\\]^{|}~??x?#");
+
+        let span = spliced.span(FileId(0), 2, 3); // ??/
+        assert_eq!((span.lo, span.hi), (6, 9));
+        let span = spliced.span(FileId(0), 9, 12); // ??x
+        assert_eq!((span.lo, span.hi), (27, 30));
+        let span = spliced.span(FileId(0), 13, 14); // ??=
+        assert_eq!((span.lo, span.hi), (31, 34));
+    }
+
+    #[test]
+    fn line_splices() {
+        let input = "#define X 1 + \\\n  2\nfo\\\r\no";
+        let spliced = splice(input);
+        assert_eq!(spliced.text(), "#define X 1 +   2\nfoo");
+
+        let span = spliced.span(FileId(0), 16, 17); // 2
+        assert_eq!(&input[span.lo..span.hi], "2");
+        let span = spliced.span(FileId(0), 18, 21); // foo
+        assert_eq!(&input[span.lo..span.hi], "fo\\\r\no");
+        let span = spliced.span(FileId(0), 12, 13); // +
+        assert_eq!(&input[span.lo..span.hi], "+");
+    }
+
+    #[test]
+    fn trigraph_splice() {
+        let input = "a??/\nb";
+        let spliced = splice(input);
+        assert_eq!(spliced.text(), "ab");
+
+        let span = spliced.span(FileId(0), 0, 1);
+        assert_eq!((span.lo, span.hi), (0, 1));
+        let span = spliced.span(FileId(0), 1, 2);
+        assert_eq!((span.lo, span.hi), (5, 6));
+    }
+}
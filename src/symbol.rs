@@ -0,0 +1,91 @@
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::{
+    collections::HashMap,
+    fmt,
+    sync::{LazyLock, Mutex},
+};
+
+/// An interned identifier or keyword. Symbols are a u32 handle, so they are
+/// Copy and compare in O(1), and each distinct name is stored once per
+/// compilation no matter how many tokens or AST nodes spell it.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Symbol(u32);
+
+// interned strings are leaked: like the source map, they live for the whole
+// (batch) compilation, which lets as_str hand out &'static str
+#[derive(Default)]
+struct Interner {
+    symbols: HashMap<&'static str, Symbol>,
+    strs: Vec<&'static str>,
+}
+
+static INTERNER: LazyLock<Mutex<Interner>> = LazyLock::new(Default::default);
+
+impl Symbol {
+    pub fn intern(s: &str) -> Symbol {
+        let mut interner = INTERNER.lock().unwrap();
+        if let Some(&sym) = interner.symbols.get(s) {
+            return sym;
+        }
+
+        let s: &'static str = Box::leak(s.into());
+        let sym = Symbol(interner.strs.len() as u32);
+        interner.strs.push(s);
+        interner.symbols.insert(s, sym);
+        sym
+    }
+
+    pub fn as_str(self) -> &'static str {
+        INTERNER.lock().unwrap().strs[self.0 as usize]
+    }
+}
+
+impl fmt::Debug for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self.as_str(), f)
+    }
+}
+
+impl fmt::Display for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl PartialEq<&str> for Symbol {
+    fn eq(&self, other: &&str) -> bool {
+        self.as_str() == *other
+    }
+}
+
+// symbols (de)serialize as the string they stand for, so snapshots stay
+// readable and don't depend on interning order
+impl Serialize for Symbol {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for Symbol {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        Ok(Symbol::intern(&s))
+    }
+}
+
+#[cfg(test)]
+mod test_interning {
+    use super::Symbol;
+
+    #[test]
+    fn intern() {
+        let a = Symbol::intern("my_var");
+        let b = Symbol::intern(&String::from("my_var"));
+        let c = Symbol::intern("my_var2");
+
+        assert_eq!(a, b);
+        assert_ne!(a, c);
+        assert_eq!(a.as_str(), "my_var");
+        assert_eq!(c, "my_var2");
+    }
+}
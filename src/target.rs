@@ -0,0 +1,146 @@
+/// The RISC-V ISA din generates code for, as named by gcc's -march: a base
+/// integer ISA and the standard extensions on top of it.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct Target {
+    pub xlen: u32,
+    pub mul: bool, // the M extension: mul, div and rem
+    pub flen: u32, // 32 with the F extension, 64 with D, 0 without either
+}
+
+impl Default for Target {
+    // the generator emits mul, div and rem for * / and %
+    fn default() -> Self {
+        Target {
+            xlen: 32,
+            mul: true,
+            flen: 0,
+        }
+    }
+}
+
+impl Target {
+    /// Parses an ISA string such as `rv32i`, `rv32im` or `rv32gc`. `g` is
+    /// short for `imafd_zicsr_zifencei`, and multi-letter extensions follow
+    /// the single-letter ones, separated by `_`. Extensions din doesn't use,
+    /// like `a`, `c` or `zicsr`, are accepted and ignored.
+    pub fn parse(march: &str) -> Option<Self> {
+        let mut parts = march.strip_prefix("rv32")?.split('_');
+        let exts = parts.next()?;
+        let exts = match exts.strip_prefix('g') {
+            Some(rest) => format!("imafd{rest}"),
+            None if exts.starts_with('i') => exts.to_owned(),
+            None => return None,
+        };
+        if !exts.chars().all(|c| c.is_ascii_lowercase()) {
+            return None;
+        }
+        for ext in parts {
+            let named = ext.starts_with(['z', 's', 'x'])
+                && ext.len() > 1
+                && ext
+                    .chars()
+                    .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit());
+            if !named {
+                return None;
+            }
+        }
+
+        Some(Target {
+            xlen: 32,
+            mul: exts.contains('m'),
+            flen: if exts.contains('d') {
+                64
+            } else if exts.contains('f') {
+                32
+            } else {
+                0
+            },
+        })
+    }
+
+    /// The macros gcc predefines for this target.
+    pub fn macros(&self) -> Vec<(&'static str, String)> {
+        let mut macros = vec![
+            ("__riscv", "1".to_owned()),
+            ("__riscv_xlen", self.xlen.to_string()),
+        ];
+        if self.mul {
+            macros.extend([
+                ("__riscv_mul", "1".to_owned()),
+                ("__riscv_div", "1".to_owned()),
+                ("__riscv_muldiv", "1".to_owned()),
+            ]);
+        }
+        if self.flen > 0 {
+            macros.extend([
+                ("__riscv_flen", self.flen.to_string()),
+                ("__riscv_fdiv", "1".to_owned()),
+                ("__riscv_fsqrt", "1".to_owned()),
+            ]);
+        }
+
+        macros
+    }
+}
+
+#[cfg(test)]
+mod test_parse {
+    use super::Target;
+
+    #[test]
+    fn march() {
+        assert_eq!(
+            Target::parse("rv32i"),
+            Some(Target {
+                xlen: 32,
+                mul: false,
+                flen: 0
+            })
+        );
+        assert_eq!(
+            Target::parse("rv32imac"),
+            Some(Target {
+                xlen: 32,
+                mul: true,
+                flen: 0
+            })
+        );
+        assert_eq!(Target::parse("rv64im"), None);
+        assert_eq!(Target::parse("rv32m"), None);
+    }
+
+    #[test]
+    fn march_g() {
+        let g = Some(Target {
+            xlen: 32,
+            mul: true,
+            flen: 64,
+        });
+        assert_eq!(Target::parse("rv32g"), g);
+        assert_eq!(Target::parse("rv32gc"), g);
+        assert_eq!(Target::parse("rv32imafd_zicsr_zifencei"), g);
+    }
+
+    #[test]
+    fn march_extensions() {
+        assert_eq!(
+            Target::parse("rv32imf_zicsr"),
+            Some(Target {
+                xlen: 32,
+                mul: true,
+                flen: 32
+            })
+        );
+        assert_eq!(
+            Target::parse("rv32i_zba_zbb"),
+            Some(Target {
+                xlen: 32,
+                mul: false,
+                flen: 0
+            })
+        );
+        assert_eq!(Target::parse("rv32i_"), None);
+        assert_eq!(Target::parse("rv32i_m"), None);
+        assert_eq!(Target::parse("rv32i_zicsr!"), None);
+    }
+}
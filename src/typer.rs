@@ -1,5 +1,3 @@
-use crate::parser;
-
 // c is statically, "weakly" typed
 // loopholes
 // - casting
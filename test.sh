@@ -3,8 +3,9 @@
 assert() {
   input="$1"
   expected="$2"
+  flags="$3"
 
-  ./target/release/din "$input" > tmp.s || exit
+  ./target/release/din $flags "$input" > tmp.s || exit
   riscv64-unknown-elf-gcc -o tmp tmp.s
   spike pk tmp
   actual="$?"
@@ -17,11 +18,29 @@ assert() {
   fi
 }
 
+# 0. lexical structure
+assert "./tests/fixtures/din/legal/comments/block.c" 8
+assert "./tests/fixtures/din/legal/splicing/trigraphs.c" 42
+assert "./tests/fixtures/din/legal/splicing/continuation.c" 42
+assert "./tests/fixtures/din/legal/preprocessor/include/main.c" 42 \
+  "-I ./tests/fixtures/din/legal/preprocessor/include/lib -isystem ./tests/fixtures/din/legal/preprocessor/include/system"
+assert "./tests/fixtures/din/legal/preprocessor/macros/regs.c" 28
+assert "./tests/fixtures/din/legal/preprocessor/conditional/config.c" 42
+assert "./tests/fixtures/din/legal/preprocessor/predefined/flags.c" 42 "-DANSWER=42"
+
 # 1. expressions
 assert "./tests/fixtures/din/legal/arithmetic/lit.c" 8
+assert "./tests/fixtures/din/legal/arithmetic/lit_hex.c" 31
+assert "./tests/fixtures/din/legal/arithmetic/lit_oct.c" 15
+assert "./tests/fixtures/din/legal/arithmetic/lit_suffix.c" 16
+assert "./tests/fixtures/din/legal/literals/char.c" 87
+assert "./tests/fixtures/din/legal/literals/float_truncate.c" 43
+assert "./tests/fixtures/din/legal/literals/float_truncate.c" 43 "-march=rv32g"
+assert "./tests/fixtures/din/legal/literals/str_arg.c" 12
 assert "./tests/fixtures/din/legal/arithmetic/add.c" 19
 assert "./tests/fixtures/din/legal/arithmetic/add_multi.c" 30
 assert "./tests/fixtures/din/legal/arithmetic/sub.c" 56
+assert "./tests/fixtures/din/legal/arithmetic/neg.c" 14
 assert "./tests/fixtures/din/legal/arithmetic/mult.c" 90
 assert "./tests/fixtures/din/legal/arithmetic/div.c" 11
 
@@ -54,11 +73,31 @@ assert "./tests/fixtures/din/legal/control_flow/gteq2_true.c" 1
 # -- conditionals
 assert "./tests/fixtures/din/legal/control_flow/ifels_then.c" 0
 assert "./tests/fixtures/din/legal/control_flow/ifels_els.c" 0
+assert "./tests/fixtures/din/legal/control_flow/if.c" 42
+assert "./tests/fixtures/din/legal/control_flow/else_if.c" 44
+assert "./tests/fixtures/din/legal/control_flow/dangling_else.c" 42
+assert "./tests/fixtures/din/legal/control_flow/block.c" 42
 
 # -- loops
-# assert "./tests/fixtures/din/legal/control_flow/for.c" 10
+assert "./tests/fixtures/din/legal/control_flow/for.c" 20
+assert "./tests/fixtures/din/legal/control_flow/for_unbraced.c" 6
+assert "./tests/fixtures/din/legal/control_flow/for_empty.c" 42
+assert "./tests/fixtures/din/legal/control_flow/for_scope.c" 42
+assert "./tests/fixtures/din/legal/control_flow/while.c" 45
+assert "./tests/fixtures/din/legal/control_flow/while_unbraced.c" 3
+assert "./tests/fixtures/din/legal/control_flow/do_while.c" 42
+assert "./tests/fixtures/din/legal/control_flow/do_while_unbraced.c" 42
+assert "./tests/fixtures/din/legal/control_flow/break.c" 42
+assert "./tests/fixtures/din/legal/control_flow/continue.c" 42
+
+# -- switches
+assert "./tests/fixtures/din/legal/control_flow/switch.c" 221
+assert "./tests/fixtures/din/legal/control_flow/switch_table.c" 92
+assert "./tests/fixtures/din/legal/control_flow/switch_tree.c" 85
+assert "./tests/fixtures/din/legal/control_flow/switch_unbraced.c" 42
 
 # -- functions
+assert "./tests/fixtures/din/legal/functions/calls.c" 42
 
 
 
@@ -0,0 +1,8 @@
+#ifdef X
+#else
+#elif 1
+#else
+#endif
+int main() {
+  return 0;
+}
\ No newline at end of file
@@ -0,0 +1,19 @@
+#if
+#endif
+#if 1 +
+#endif
+#if (1
+#endif
+#if 1 2
+#endif
+#if defined
+#endif
+#if 1.5
+#endif
+#if 0x10000000000000000
+#endif
+#if 'a
+#endif
+int main() {
+  return 0;
+}
\ No newline at end of file
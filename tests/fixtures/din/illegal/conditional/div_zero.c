@@ -0,0 +1,7 @@
+#if 1 / 0
+#endif
+#if 0 && 1 / 0
+#endif
+int main() {
+  return 0;
+}
\ No newline at end of file
@@ -0,0 +1,5 @@
+#else
+#endif
+int main() {
+  return 0;
+}
\ No newline at end of file
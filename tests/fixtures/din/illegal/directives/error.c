@@ -0,0 +1,7 @@
+#define XLEN 64
+
+#if XLEN != 32
+#error din can't target rv64: XLEN is XLEN
+#endif
+
+int x = 'unterminated;
\ No newline at end of file
@@ -0,0 +1,6 @@
+#line 0
+#line 0x10
+#line 40000
+#line 5 foo
+#line
+#line 7 "a.c" 8
\ No newline at end of file
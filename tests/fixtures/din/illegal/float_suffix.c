@@ -0,0 +1,3 @@
+int main() {
+  return 1.5x;
+}
\ No newline at end of file
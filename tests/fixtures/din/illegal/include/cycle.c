@@ -0,0 +1,4 @@
+int main() {
+#include "a.h"
+  return 0;
+}
\ No newline at end of file
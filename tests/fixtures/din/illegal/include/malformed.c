@@ -0,0 +1,4 @@
+int main() {
+#include missing.h
+  return 0;
+}
\ No newline at end of file
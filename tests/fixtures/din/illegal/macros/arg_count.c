@@ -0,0 +1,4 @@
+#define f(a, b) a + b
+int main() {
+  return f(1);
+}
\ No newline at end of file
@@ -0,0 +1,4 @@
+#define cat(a, b) a ## b
+int main() {
+  return cat(+, /);
+}
\ No newline at end of file
@@ -0,0 +1,6 @@
+#define f(a, a) a
+#define g(a,) a
+#define 1 a
+int main() {
+  return 0;
+}
\ No newline at end of file
@@ -0,0 +1,2 @@
+#define N 08
+int x = N;
\ No newline at end of file
@@ -0,0 +1,5 @@
+#define N 1
+#define N 2
+int main() {
+  return N;
+}
\ No newline at end of file
@@ -0,0 +1,4 @@
+#define str(s) #t
+int main() {
+  return 0;
+}
\ No newline at end of file
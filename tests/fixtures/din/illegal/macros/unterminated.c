@@ -0,0 +1,4 @@
+#define f(a) a
+int main() {
+  return f(1;
+}
\ No newline at end of file
@@ -0,0 +1,3 @@
+int main() {
+  return 09;
+}
\ No newline at end of file
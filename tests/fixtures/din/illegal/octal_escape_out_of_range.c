@@ -0,0 +1,3 @@
+int main() {
+  return '\400';
+}
\ No newline at end of file
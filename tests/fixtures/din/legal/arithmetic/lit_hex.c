@@ -0,0 +1,3 @@
+int main() {
+  return 0x1F;
+}
\ No newline at end of file
@@ -0,0 +1,3 @@
+int main() {
+  return 10UL + 0xFFFFFFFF + 7u + 2147483648;
+}
\ No newline at end of file
@@ -0,0 +1,3 @@
+int main() {
+  return -3 * -4 + +2;
+}
\ No newline at end of file
@@ -0,0 +1,6 @@
+/* a block comment
+ * spanning lines */
+int main() {
+    int x = 8; /* trailing */
+    return /* inline */ x;
+}
@@ -0,0 +1,4 @@
+// a line comment
+int main() {
+    return 9; // trailing /* not a block */
+}
@@ -0,0 +1,11 @@
+int main() {
+  int n = 40;
+  {
+    int n = 1;
+    n += 1;
+  }
+  {
+    n += 2;
+  }
+  return n;
+}
\ No newline at end of file
@@ -0,0 +1,12 @@
+int main() {
+  int n = 0;
+  while (1) {
+    n += 1;
+    if (n < 42) {
+      continue;
+    } else {
+      break;
+    }
+  }
+  return n;
+}
\ No newline at end of file
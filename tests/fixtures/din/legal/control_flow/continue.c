@@ -0,0 +1,11 @@
+int main() {
+  int n = 0;
+  for (int i = 0; i < 10; i++) {
+    if (i < 3) {
+      continue;
+    } else {
+      n += i;
+    }
+  }
+  return n;
+}
\ No newline at end of file
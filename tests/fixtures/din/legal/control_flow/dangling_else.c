@@ -0,0 +1,9 @@
+int main() {
+  int n = 0;
+  if (1)
+    if (0)
+      n += 1;
+    else
+      n += 42;
+  return n;
+}
\ No newline at end of file
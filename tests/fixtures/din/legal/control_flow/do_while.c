@@ -0,0 +1,7 @@
+int main() {
+  int n = 0;
+  do {
+    n += 7;
+  } while (n < 40);
+  return n;
+}
\ No newline at end of file
@@ -0,0 +1,7 @@
+int main() {
+  int x = 0;
+  do
+    x += 7;
+  while (x < 40);
+  return x;
+}
\ No newline at end of file
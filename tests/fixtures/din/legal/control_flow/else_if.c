@@ -0,0 +1,14 @@
+int f(int x) {
+  if (x < 10)
+    return 1;
+  else if (x < 20)
+    return 2;
+  else if (x < 30)
+    return 3;
+  else
+    return 4;
+}
+
+int main() {
+  return f(5) + f(15) * 10 + f(25) + f(35) * 5;
+}
\ No newline at end of file
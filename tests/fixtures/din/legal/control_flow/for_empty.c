@@ -0,0 +1,7 @@
+int main() {
+  int n = 0;
+  for (; n < 42;) {
+    n += 6;
+  }
+  return n;
+}
\ No newline at end of file
@@ -0,0 +1,8 @@
+int main() {
+  int i = 40;
+  int n = 0;
+  for (int i = 0; i < 2; i++) {
+    n += 1;
+  }
+  return i + n;
+}
\ No newline at end of file
@@ -0,0 +1,7 @@
+int main() {
+  int n = 0;
+  int i = 0;
+  for (i = 0; i < 3; i++)
+    n += 1;
+  return n + i;
+}
\ No newline at end of file
@@ -0,0 +1,9 @@
+int main() {
+  int n = 40;
+  if (n < 42)
+    n += 2;
+  if (n < 42) {
+    n += 100;
+  }
+  return n;
+}
\ No newline at end of file
@@ -0,0 +1,15 @@
+int main() {
+  int n = 0;
+  for (int i = 0; i < 4; i++) {
+    switch (i) {
+      case 0:
+        n += 1;
+      case 1:
+        n += 10;
+        break;
+      default:
+        n += 100;
+    }
+  }
+  return n;
+}
\ No newline at end of file
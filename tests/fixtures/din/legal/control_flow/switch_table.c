@@ -0,0 +1,19 @@
+int f(int x) {
+  switch (x) {
+    case 1:
+      return 10;
+    case 2:
+      return 20;
+    case 3:
+      return 30;
+    case 5:
+      return 50;
+    default:
+      return 1;
+  }
+  return 0;
+}
+
+int main() {
+  return f(1) + f(3) + f(4) + f(5) + f(9);
+}
\ No newline at end of file
@@ -0,0 +1,23 @@
+int f(int x) {
+  switch (x) {
+    case 1:
+      return 1;
+    case 10:
+      return 2;
+    case 100:
+      return 3;
+    case 1000:
+      return 4;
+    case 10000:
+      return 5;
+    case -7:
+      return 6;
+    case 500:
+      return 7;
+  }
+  return 0;
+}
+
+int main() {
+  return f(-7) * 10 + f(1000) + f(3) + f(500) * 3;
+}
\ No newline at end of file
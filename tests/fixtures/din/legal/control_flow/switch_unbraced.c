@@ -0,0 +1,7 @@
+int main() {
+  int n = 0;
+  switch (n)
+    case 0:
+      n = 42;
+  return n;
+}
\ No newline at end of file
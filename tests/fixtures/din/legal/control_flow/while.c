@@ -0,0 +1,9 @@
+int main() {
+  int n = 0;
+  int i = 0;
+  while (i < 10) {
+    n += i;
+    i++;
+  }
+  return n;
+}
\ No newline at end of file
@@ -0,0 +1,6 @@
+int main() {
+  int i = 0;
+  while (i < 3)
+    i++;
+  return i;
+}
\ No newline at end of file
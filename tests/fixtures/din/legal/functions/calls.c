@@ -0,0 +1,15 @@
+void nothing() {
+}
+
+int add(int a, int b) {
+  return a + b;
+}
+
+int sum(int a, int b, int c, int d, int e, int f, int g, int h, int i, int j) {
+  return a + b + c + d + e + f + g + h + i + j;
+}
+
+int main() {
+  nothing();
+  return add(sum(1, 2, 3, 4, 5, 6, 7, 8, 9, 10), 2) - 15;
+}
\ No newline at end of file
@@ -0,0 +1,14 @@
+int add(int a, int b) {
+  return a + b;
+}
+
+long unsigned int scale(unsigned x, signed char *s, double **m) {
+  return x;
+}
+
+void nothing(void) {
+}
+
+int main() {
+  return 0;
+}
\ No newline at end of file
@@ -0,0 +1,3 @@
+int main() {
+  return 'a' - '\n' + '\x41' - '\101' + '\\' - 92;
+}
\ No newline at end of file
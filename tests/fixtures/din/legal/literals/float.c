@@ -0,0 +1,3 @@
+int main() {
+  return 1.5 + .5e-3 + 2.0f + 1e10L;
+}
\ No newline at end of file
@@ -0,0 +1,3 @@
+int main() {
+  return 2.75 + 40.5f;
+}
\ No newline at end of file
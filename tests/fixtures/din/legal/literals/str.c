@@ -0,0 +1,3 @@
+int main() {
+  return "hello, " "world\n\t\"\0";
+}
\ No newline at end of file
@@ -0,0 +1,3 @@
+int main() {
+  return strlen("hello, " "world");
+}
\ No newline at end of file
@@ -0,0 +1,12 @@
+#define DEBUG 0
+#define LEVEL 3
+
+int main() {
+#if DEBUG
+  return 1;
+#elif LEVEL >= 3
+  return 42;
+#else
+  return 2;
+#endif
+}
\ No newline at end of file
@@ -0,0 +1,35 @@
+#define A 2
+#define B
+#if A * 3 == 6 && defined B && !defined(C)
+int a;
+#elif 1
+int wrong;
+#endif
+#if -1 > 0u
+int b;
+#endif
+#if 0
+it's skipped ' @ ` "
+#  if 1
+int wrong;
+#  else
+int wrong;
+#  endif
+#elif UNKNOWN
+int wrong;
+#elif (1 ? 0 : 1 / 0) || 0x10 >> 4 == 1
+int c;
+#else
+int wrong;
+#endif
+#ifdef A
+int d;
+#endif
+#ifndef A
+int wrong;
+#else
+int e;
+#endif
+#if 0x7fffffffffffffff > 0 && 18446744073709551615u == -1
+int f;
+#endif
\ No newline at end of file
@@ -0,0 +1,15 @@
+#pragma once
+#pragma STDC FP_CONTRACT ON
+#pragma pack(4)
+#pragma din unroll
+#warning don't ship this
+
+int a = __LINE__;
+#line 100
+int b = __LINE__;
+#line 200 "generated.c"
+const char *f = __FILE__;
+int c = __LINE__;
+#define N 300
+#line N
+int d = __LINE__;
\ No newline at end of file
@@ -0,0 +1,6 @@
+#warning split \
+across lines
+#warning and ??/
+by a trigraph
+
+int x;
\ No newline at end of file
@@ -0,0 +1,2 @@
+#include "guarded_else.h"
+#include "guarded_else.h"
\ No newline at end of file
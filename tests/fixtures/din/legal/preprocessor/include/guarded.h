@@ -0,0 +1,6 @@
+#ifndef GUARDED_H
+#define GUARDED_H
+
+  int a = 1;
+
+#endif
\ No newline at end of file
@@ -0,0 +1,6 @@
+#ifndef GUARDED_ELSE_H
+#define GUARDED_ELSE_H
+  int first;
+#else
+  int second;
+#endif
\ No newline at end of file
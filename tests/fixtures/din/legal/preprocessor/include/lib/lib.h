@@ -0,0 +1 @@
+  int c = 3;
\ No newline at end of file
@@ -0,0 +1,9 @@
+int main() {
+#include "guarded.h"
+#include "guarded.h"
+#include "once.h"
+#include "once.h"
+#include "lib.h"
+#include <sys.h>
+  return a + b + c + d;
+}
\ No newline at end of file
@@ -0,0 +1,3 @@
+#pragma once
+
+  int b = 2;
\ No newline at end of file
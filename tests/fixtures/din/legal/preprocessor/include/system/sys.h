@@ -0,0 +1 @@
+  int d = 36;
\ No newline at end of file
@@ -0,0 +1,4 @@
+#define BIG 0x100000000
+#define S(x) #x
+#define Q(x) #x
+S(1.2.3) S(0x) S(08) Q('\q')
\ No newline at end of file
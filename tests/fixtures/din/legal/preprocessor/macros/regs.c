@@ -0,0 +1,9 @@
+#define BASE 0x10
+#define REG(n) \
+  (BASE + (n) * 4)
+#define CAT(a, b) a ## b
+
+int main() {
+  int CAT(r, eg) = REG(2 + 1);
+  return reg;
+}
\ No newline at end of file
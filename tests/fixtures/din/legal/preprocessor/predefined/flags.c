@@ -0,0 +1,7 @@
+#ifndef ANSWER
+#error ANSWER is not defined
+#endif
+
+int main() {
+  return ANSWER;
+}
\ No newline at end of file
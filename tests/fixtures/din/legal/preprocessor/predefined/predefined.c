@@ -0,0 +1,15 @@
+#if __STDC__ && __riscv && __riscv_xlen == 32 && defined(__riscv_mul)
+int a = __LINE__;
+char *f = __FILE__;
+char *d = __DATE__;
+char *t = __TIME__;
+#endif
+#ifdef NDEBUG
+int n = LEVEL;
+#endif
+#ifdef DEBUG
+int wrong;
+#endif
+#define LINE __LINE__
+int l =
+  LINE;
\ No newline at end of file
@@ -0,0 +1,5 @@
+int ma\
+in() {
+  return 4\
+2;
+}
\ No newline at end of file